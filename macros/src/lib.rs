@@ -0,0 +1,286 @@
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use syn::{
+    parse::{Parse, ParseStream},
+    parse_macro_input,
+    spanned::Spanned,
+    Error, Expr, FnArg, Ident, ItemFn, LitInt, LitStr, Pat, Token, Type,
+};
+
+// Route attribute.
+// --------------------------------------------------
+//
+// Declares an async function as a function route, for example:
+//
+//   #[route(post, "orders/create", access = User, validator = validate_order)]
+//   async fn create_order(
+//       order: CreateOrder,
+//       #[query] query: OrderQuery,
+//   ) -> Result<Order, ServerError> { ... }
+//
+// The request data type is taken from the first argument without a #[query]
+// or #[path] attribute (if any). The generated handler parses and validates
+// the request the same way as register_function_route!, and is registered
+// (together with the request data schema, see RequestSchema) so that
+// RoutingConfig::from_registered_routes() picks it up.
+//
+// Supported options (all optional):
+//   - access = Guest | User | Admin | None (default User)
+//   - validator = <sync validator, as in register_function_route!>
+//   - async_validator = <AsyncValidator expression>
+//   - state = <reference to the async validator's shared state>
+//   - timeout_ms = <handler timeout in milliseconds>
+
+#[proc_macro_attribute]
+pub fn route(args: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(args as RouteArgs);
+    let item = parse_macro_input!(item as ItemFn);
+    expand_route(args, item)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+struct RouteArgs {
+    path: LitStr,
+    access: Option<Expr>,
+    validator: Option<Expr>,
+    async_validator: Option<Expr>,
+    state: Option<Expr>,
+    timeout_ms: Option<LitInt>,
+}
+
+impl Parse for RouteArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let method: Ident = input.parse()?;
+        if method != "post" {
+            return Err(Error::new(
+                method.span(),
+                "function routes only support the 'post' method",
+            ));
+        }
+        input.parse::<Token![,]>()?;
+        let mut args = RouteArgs {
+            path: input.parse()?,
+            access: None,
+            validator: None,
+            async_validator: None,
+            state: None,
+            timeout_ms: None,
+        };
+        while !input.is_empty() {
+            input.parse::<Token![,]>()?;
+            if input.is_empty() {
+                break;
+            }
+            let key: Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+            match key.to_string().as_str() {
+                "access" => args.access = Some(input.parse()?),
+                "validator" => args.validator = Some(input.parse()?),
+                "async_validator" => args.async_validator = Some(input.parse()?),
+                "state" => args.state = Some(input.parse()?),
+                "timeout_ms" => args.timeout_ms = Some(input.parse()?),
+                other => {
+                    return Err(Error::new(
+                        key.span(),
+                        format!("unknown route option '{}'", other),
+                    ))
+                }
+            }
+        }
+        if args.validator.is_some() && args.async_validator.is_some() {
+            return Err(Error::new(
+                Span::call_site(),
+                "'validator' and 'async_validator' cannot be combined",
+            ));
+        }
+        if args.state.is_some() && args.async_validator.is_none() {
+            return Err(Error::new(
+                Span::call_site(),
+                "'state' requires an 'async_validator'",
+            ));
+        }
+        Ok(args)
+    }
+}
+
+enum ArgKind {
+    Data,
+    Query,
+    Path,
+}
+
+struct RouteArg {
+    kind: ArgKind,
+    ident: Ident,
+    ty: Type,
+}
+
+fn expand_route(args: RouteArgs, mut item: ItemFn) -> syn::Result<TokenStream2> {
+    if item.sig.asyncness.is_none() {
+        return Err(Error::new(item.sig.fn_token.span(), "routes must be async"));
+    }
+
+    // Classify the arguments, removing the #[query] and #[path] markers.
+    let mut route_args = Vec::new();
+    for (i, input) in item.sig.inputs.iter_mut().enumerate() {
+        let FnArg::Typed(arg) = input else {
+            return Err(Error::new(input.span(), "routes cannot take self"));
+        };
+        let mut kind = ArgKind::Data;
+        arg.attrs.retain(|attr| {
+            if attr.path().is_ident("query") {
+                kind = ArgKind::Query;
+                false
+            } else if attr.path().is_ident("path") {
+                kind = ArgKind::Path;
+                false
+            } else {
+                true
+            }
+        });
+        let ident = match &*arg.pat {
+            Pat::Ident(pat) => pat.ident.clone(),
+            _ => format_ident!("arg{}", i),
+        };
+        route_args.push(RouteArg {
+            kind,
+            ident,
+            ty: (*arg.ty).clone(),
+        });
+    }
+    let data_args: Vec<&RouteArg> = route_args
+        .iter()
+        .filter(|a| matches!(a.kind, ArgKind::Data))
+        .collect();
+    if data_args.len() > 1 {
+        return Err(Error::new(
+            data_args[1].ty.span(),
+            "only one request data argument is allowed (mark query string and path \
+             parameters with #[query] or #[path])",
+        ));
+    }
+    let data_arg = data_args.first();
+
+    let krate = quote!(::fractic_aws_apigateway);
+    let func = &item.sig.ident;
+    let handler = format_ident!("__route_handler_{}", func);
+    let path = &args.path;
+
+    // Argument parsing, in declaration order.
+    let parse_args = route_args.iter().map(|arg| {
+        let (ident, ty) = (&arg.ident, &arg.ty);
+        let parse = match arg.kind {
+            ArgKind::Data => quote!(#krate::parse_request_body!(#ty, &event.payload)),
+            ArgKind::Query => quote!(#krate::parse_query::<#ty>(&event.payload)),
+            ArgKind::Path => quote!(#krate::parse_path_params::<#ty>(&event.payload)),
+        };
+        let validate = match arg.kind {
+            ArgKind::Data => quote! {
+                if let Err(validation_error) = #krate::validate_request_data!(#ident) {
+                    return #krate::build_error(validation_error);
+                }
+            },
+            _ => quote!(),
+        };
+        quote! {
+            let #ident = match #parse {
+                Ok(value) => value,
+                Err(parsing_error) => return #krate::build_error(parsing_error),
+            };
+            #validate
+        }
+    });
+    let call_args = route_args.iter().map(|arg| &arg.ident);
+
+    let validate = match (&args.validator, &args.async_validator) {
+        (Some(validator), _) => {
+            let validator_args = match data_arg {
+                Some(arg) => {
+                    let ident = &arg.ident;
+                    quote!(&#ident, metadata)
+                }
+                None => quote!(metadata),
+            };
+            quote! {
+                if let Err(validation_error) = #validator(#validator_args) {
+                    return #krate::build_error(validation_error);
+                }
+            }
+        }
+        (None, Some(validator)) => {
+            let data = match data_arg {
+                Some(arg) => {
+                    let ident = &arg.ident;
+                    quote!(&#ident)
+                }
+                None => quote!(&()),
+            };
+            let state = args.state.as_ref().map(|state| quote!(, #state));
+            quote! {
+                if let Err(validation_error) = #krate::run_async_validator!(
+                    #validator,
+                    &event.payload,
+                    &metadata,
+                    #data
+                    #state
+                ) {
+                    return #krate::build_error(validation_error);
+                }
+            }
+        }
+        (None, None) => quote!(),
+    };
+
+    let access = match &args.access {
+        Some(Expr::Path(p)) if p.path.get_ident().is_some() => {
+            quote!(#krate::AccessLevel::#p)
+        }
+        Some(access) => quote!(#access),
+        None => quote!(#krate::AccessLevel::User),
+    };
+    let request_schema = match data_arg {
+        Some(arg) => {
+            let ty = &arg.ty;
+            quote!(|| #krate::request_schema_of!(#ty))
+        }
+        None => quote!(#krate::RequestSchema::none),
+    };
+    let timeout = match &args.timeout_ms {
+        Some(ms) => quote!(Some(::std::time::Duration::from_millis(#ms))),
+        None => quote!(None),
+    };
+
+    Ok(quote! {
+        #item
+
+        #[doc(hidden)]
+        #[allow(unused_variables)]
+        fn #handler(
+            event: #krate::__private::lambda_runtime::LambdaEvent<
+                #krate::__private::aws_lambda_events::apigw::ApiGatewayProxyRequest,
+            >,
+            metadata: #krate::RequestMetadata,
+        ) -> #krate::RouteFuture {
+            Box::pin(async move {
+                #(#parse_args)*
+                #validate
+                match #func(#(#call_args),*).await {
+                    Ok(result) => #krate::IntoApiResponse::into_api_response(result),
+                    Err(func_error) => #krate::build_error(func_error),
+                }
+            })
+        }
+
+        #krate::__private::inventory::submit! {
+            #krate::RouteRegistration {
+                path: #path,
+                access_level: #access,
+                handler: #handler,
+                timeout: #timeout,
+                request_schema: #request_schema,
+            }
+        }
+    })
+}
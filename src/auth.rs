@@ -47,43 +47,36 @@ pub fn get_sub_of_authenticated_user(req: &ApiGatewayProxyRequest) -> Result<Str
     }
 }
 
+// Claims in the format of a Cognito user pool authorizer, as read by the
+// functions above. Used to fake identities in local development and tests.
+pub fn fake_cognito_claims(sub: &str, username: &str, groups: &[&str]) -> serde_json::Value {
+    serde_json::json!({
+        "sub": sub,
+        "cognito:username": username,
+        "cognito:groups": groups.join(","),
+    })
+}
+
 // Tests.
 // --------------------------------------------------
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use aws_lambda_events::apigw::{
-        ApiGatewayProxyRequest, ApiGatewayProxyRequestContext, ApiGatewayRequestAuthorizer,
-    };
+    use crate::testing::TestRequest;
 
     fn create_authenticated_request() -> ApiGatewayProxyRequest {
-        ApiGatewayProxyRequest {
-            request_context: ApiGatewayProxyRequestContext {
-                authorizer: ApiGatewayRequestAuthorizer {
-                    fields: [(
-                        "claims".into(),
-                        serde_json::json!({
-                            "cognito:username": "FakeUsername",
-                            "sub": "FakeUserSub"
-                        }),
-                    )]
-                    .into(),
-                    ..Default::default()
-                },
-                ..Default::default()
-            },
-            ..Default::default()
-        }
+        TestRequest::post("test")
+            .with_claims(serde_json::json!({
+                "cognito:username": "FakeUsername",
+                "sub": "FakeUserSub"
+            }))
+            .into_event()
+            .payload
     }
 
     fn create_unauthenticated_request() -> ApiGatewayProxyRequest {
-        ApiGatewayProxyRequest {
-            request_context: ApiGatewayProxyRequestContext {
-                ..Default::default()
-            },
-            ..Default::default()
-        }
+        TestRequest::post("test").as_guest().into_event().payload
     }
 
     #[test]
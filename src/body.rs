@@ -0,0 +1,395 @@
+use std::io::Read;
+
+use aws_lambda_events::{
+    apigw::ApiGatewayProxyRequest,
+    http::header::{CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE},
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use flate2::read::{DeflateDecoder, GzDecoder, ZlibDecoder};
+use fractic_server_error::ServerError;
+
+use crate::{
+    errors::{InvalidRequestError, PayloadTooLargeError, UnsupportedMediaTypeError},
+    form::{multipart_boundary, parse_multipart},
+};
+
+const UNSUPPORTED_MEDIA_TYPE: i64 = 415;
+const PAYLOAD_TOO_LARGE: i64 = 413;
+const BAD_REQUEST: i64 = 400;
+
+// Limit on the decompressed size of routes without a max_body_size, so that a
+// small compressed body cannot expand without bound (a 'zip bomb'). Matches
+// Lambda's own 6 MB limit on the (compressed) request payload.
+const DEFAULT_MAX_DECOMPRESSED_SIZE: usize = 6 * 1024 * 1024;
+
+// Request body config.
+// --------------------------------------------------
+
+// Per-route restrictions on the request body, enforced by handle_route before
+// the handler is called.
+#[derive(Debug, Clone, Default)]
+pub struct RequestBodyConfig {
+    // Maximum size of the body in bytes, applied both before and after
+    // decompression. If None, only API Gateway's own limit applies to the raw
+    // body, and the decompressed body is limited to 6 MB.
+    pub max_body_size: Option<usize>,
+    // Accepted media types (for example "application/json" or "image/*"). If
+    // empty, any content type is accepted.
+    pub content_types: Vec<String>,
+    // Maximum size in bytes of each part of a multipart/form-data body.
+    pub max_part_size: Option<usize>,
+}
+
+impl RequestBodyConfig {
+    pub fn json() -> Self {
+        RequestBodyConfig {
+            max_body_size: None,
+            content_types: vec!["application/json".to_string()],
+            max_part_size: None,
+        }
+    }
+
+    pub fn with_max_body_size(mut self, max_body_size: usize) -> Self {
+        self.max_body_size = Some(max_body_size);
+        self
+    }
+
+    pub fn with_content_type(mut self, content_type: &str) -> Self {
+        self.content_types.push(content_type.to_string());
+        self
+    }
+
+    pub fn with_max_part_size(mut self, max_part_size: usize) -> Self {
+        self.max_part_size = Some(max_part_size);
+        self
+    }
+}
+
+// Request body utils.
+// --------------------------------------------------
+
+// Enforces the route's body config, and normalizes the body so that handlers
+// do not need to deal with transport encodings: base64 bodies are decoded and
+// compressed bodies (Content-Encoding: gzip / deflate) are decompressed. If
+// the result is valid UTF-8 it is stored as plain text, otherwise it is stored
+// base64-encoded with is_base64_encoded set.
+pub fn prepare_request_body(
+    request: &mut ApiGatewayProxyRequest,
+    config: &RequestBodyConfig,
+) -> Result<(), ServerError> {
+    prepare_request_body_with_status(request, config).map_err(|(_, error)| error)
+}
+
+// Same as prepare_request_body, but also returns the HTTP status code which
+// handle_route should respond with if the body is rejected.
+pub(crate) fn prepare_request_body_with_status(
+    request: &mut ApiGatewayProxyRequest,
+    config: &RequestBodyConfig,
+) -> Result<(), (i64, ServerError)> {
+    let Some(body) = request.body.take() else {
+        return Ok(());
+    };
+
+    if !config.content_types.is_empty() {
+        let content_type = content_type(request);
+        if !config
+            .content_types
+            .iter()
+            .any(|accepted| media_type_matches(accepted, content_type))
+        {
+            return Err((
+                UNSUPPORTED_MEDIA_TYPE,
+                UnsupportedMediaTypeError::new(&format!(
+                    "content type '{}' is not one of [{}]",
+                    content_type,
+                    config.content_types.join(", ")
+                )),
+            ));
+        }
+    }
+
+    let raw = if request.is_base64_encoded {
+        BASE64.decode(body.trim()).map_err(|e| {
+            (
+                BAD_REQUEST,
+                InvalidRequestError::with_debug("body is not valid base64", &e),
+            )
+        })?
+    } else {
+        body.into_bytes()
+    };
+    check_size(raw.len(), config.max_body_size)?;
+
+    let encodings: Vec<String> = request
+        .headers
+        .get(CONTENT_ENCODING)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| {
+            v.split(',')
+                .map(|e| e.trim().to_lowercase())
+                .filter(|e| !e.is_empty() && e != "identity")
+                .collect()
+        })
+        .unwrap_or_default();
+    // Encodings are listed in the order they were applied, so they are
+    // undone in reverse.
+    let mut decoded = raw;
+    for encoding in encodings.iter().rev() {
+        decoded = decompress(
+            &decoded,
+            encoding,
+            config
+                .max_body_size
+                .unwrap_or(DEFAULT_MAX_DECOMPRESSED_SIZE),
+        )?;
+    }
+    if !encodings.is_empty() {
+        request.headers.remove(CONTENT_ENCODING);
+        request.headers.remove(CONTENT_LENGTH);
+    }
+
+    if let Some(max_part_size) = config.max_part_size {
+        if media_type_matches("multipart/form-data", content_type(request)) {
+            let boundary = multipart_boundary(request).map_err(|e| (BAD_REQUEST, e))?;
+            let parts = parse_multipart(&decoded, &boundary).map_err(|e| (BAD_REQUEST, e))?;
+            if let Some(part) = parts.iter().find(|p| p.data.len() > max_part_size) {
+                return Err((
+                    PAYLOAD_TOO_LARGE,
+                    PayloadTooLargeError::new(&format!(
+                        "part '{}' is larger than the limit of {} bytes",
+                        part.name, max_part_size
+                    )),
+                ));
+            }
+        }
+    }
+
+    match String::from_utf8(decoded) {
+        Ok(text) => {
+            request.body = Some(text);
+            request.is_base64_encoded = false;
+        }
+        Err(e) => {
+            request.body = Some(BASE64.encode(e.into_bytes()));
+            request.is_base64_encoded = true;
+        }
+    }
+    Ok(())
+}
+
+// Returns the body as bytes, decoding it if it is base64-encoded.
+pub fn request_body_bytes(request: &ApiGatewayProxyRequest) -> Result<Vec<u8>, ServerError> {
+    match &request.body {
+        Some(body) if request.is_base64_encoded => BASE64
+            .decode(body.trim())
+            .map_err(|e| InvalidRequestError::with_debug("body is not valid base64", &e)),
+        Some(body) => Ok(body.clone().into_bytes()),
+        None => Err(InvalidRequestError::new("missing request body")),
+    }
+}
+
+// Helper functions.
+// --------------------------------------------------
+
+fn content_type(request: &ApiGatewayProxyRequest) -> &str {
+    request
+        .headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+}
+
+fn media_type_matches(accepted: &str, content_type: &str) -> bool {
+    // Ignore parameters such as "; charset=utf-8".
+    let essence = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase();
+    let accepted = accepted.trim().to_lowercase();
+    match accepted.strip_suffix("/*") {
+        Some(accepted_type) => essence
+            .split('/')
+            .next()
+            .is_some_and(|t| t == accepted_type),
+        None => essence == accepted,
+    }
+}
+
+fn check_size(size: usize, max_body_size: Option<usize>) -> Result<(), (i64, ServerError)> {
+    match max_body_size {
+        Some(max) if size > max => Err((
+            PAYLOAD_TOO_LARGE,
+            PayloadTooLargeError::new(&format!("body is larger than the limit of {} bytes", max)),
+        )),
+        _ => Ok(()),
+    }
+}
+
+// Zlib header: the compression method (CM) must be deflate, and the first two
+// bytes must be a multiple of 31 (FCHECK).
+fn has_zlib_header(data: &[u8]) -> bool {
+    match data {
+        [cmf, flg, ..] => cmf & 0x0f == 8 && (u16::from(*cmf) << 8 | u16::from(*flg)) % 31 == 0,
+        _ => false,
+    }
+}
+
+fn decompress(
+    data: &[u8],
+    encoding: &str,
+    max_body_size: usize,
+) -> Result<Vec<u8>, (i64, ServerError)> {
+    let reader: Box<dyn Read + '_> = match encoding {
+        "gzip" | "x-gzip" => Box::new(GzDecoder::new(data)),
+        // Although 'deflate' should be zlib-wrapped, some clients send raw
+        // deflate streams, so check for the zlib header.
+        "deflate" if has_zlib_header(data) => Box::new(ZlibDecoder::new(data)),
+        "deflate" => Box::new(DeflateDecoder::new(data)),
+        other => {
+            return Err((
+                UNSUPPORTED_MEDIA_TYPE,
+                UnsupportedMediaTypeError::new(&format!(
+                    "content encoding '{}' is not supported",
+                    other
+                )),
+            ))
+        }
+    };
+    // Read at most one byte over the limit, to detect oversized bodies without
+    // decompressing them entirely.
+    let mut reader = reader.take(max_body_size as u64 + 1);
+    let mut decoded = Vec::new();
+    reader.read_to_end(&mut decoded).map_err(|e| {
+        (
+            BAD_REQUEST,
+            InvalidRequestError::with_debug(&format!("body is not valid {}", encoding), &e),
+        )
+    })?;
+    check_size(decoded.len(), Some(max_body_size))?;
+    Ok(decoded)
+}
+
+// Tests.
+// --------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{
+        write::{GzEncoder, ZlibEncoder},
+        Compression,
+    };
+    use std::io::Write;
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn create_request(content_type: &str, body: &str) -> ApiGatewayProxyRequest {
+        let mut request = ApiGatewayProxyRequest {
+            body: Some(body.to_string()),
+            ..Default::default()
+        };
+        request
+            .headers
+            .insert(CONTENT_TYPE, content_type.parse().unwrap());
+        request
+    }
+
+    #[test]
+    fn test_content_type_enforced() {
+        let config = RequestBodyConfig::json();
+        let mut request = create_request("application/json; charset=utf-8", "{}");
+        assert!(prepare_request_body(&mut request, &config).is_ok());
+
+        let mut request = create_request("text/plain", "{}");
+        let err = prepare_request_body(&mut request, &config).unwrap_err();
+        assert!(format!("{:?}", err).contains("UnsupportedMediaTypeError"));
+    }
+
+    #[test]
+    fn test_wildcard_content_type() {
+        assert!(media_type_matches("image/*", "image/png"));
+        assert!(!media_type_matches("image/*", "application/json"));
+    }
+
+    #[test]
+    fn test_base64_gzip_body_decoded() {
+        let mut request = create_request("application/json", "");
+        request.body = Some(BASE64.encode(gzip(b"{\"key\":\"value\"}")));
+        request.is_base64_encoded = true;
+        request
+            .headers
+            .insert(CONTENT_ENCODING, "gzip".parse().unwrap());
+
+        prepare_request_body(&mut request, &RequestBodyConfig::default()).unwrap();
+        assert_eq!(request.body.as_deref(), Some("{\"key\":\"value\"}"));
+        assert!(!request.is_base64_encoded);
+        assert!(request.headers.get(CONTENT_ENCODING).is_none());
+    }
+
+    #[test]
+    fn test_max_body_size_applies_after_decompression() {
+        let mut request = create_request("application/json", "");
+        request.body = Some(BASE64.encode(gzip(&[b' '; 1000])));
+        request.is_base64_encoded = true;
+        request
+            .headers
+            .insert(CONTENT_ENCODING, "gzip".parse().unwrap());
+
+        let config = RequestBodyConfig::default().with_max_body_size(100);
+        let err = prepare_request_body(&mut request, &config).unwrap_err();
+        assert!(format!("{:?}", err).contains("PayloadTooLargeError"));
+    }
+
+    #[test]
+    fn test_decompressed_size_limited_by_default() {
+        // 16 MB of zeros compresses to well under 100 KB.
+        let bomb = gzip(&vec![0; 16 * 1024 * 1024]);
+        assert!(bomb.len() < 100 * 1024);
+
+        let mut request = create_request("application/octet-stream", "");
+        request.body = Some(BASE64.encode(bomb));
+        request.is_base64_encoded = true;
+        request
+            .headers
+            .insert(CONTENT_ENCODING, "gzip".parse().unwrap());
+        let err = prepare_request_body_with_status(&mut request, &RequestBodyConfig::default())
+            .unwrap_err();
+        assert_eq!(err.0, PAYLOAD_TOO_LARGE);
+        assert!(err
+            .1
+            .message()
+            .contains(&DEFAULT_MAX_DECOMPRESSED_SIZE.to_string()));
+    }
+
+    #[test]
+    fn test_deflate_with_or_without_zlib_header() {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"hello").unwrap();
+        let zlib = encoder.finish().unwrap();
+        assert_eq!(decompress(&zlib, "deflate", 1024).unwrap(), b"hello");
+
+        // Raw deflate stream whose first byte looks like a zlib CMF byte (a
+        // non-final stored block with a padding bit set), followed by an empty
+        // final stored block.
+        let mut raw = vec![0x08, 0x05, 0x00, 0xfa, 0xff];
+        raw.extend_from_slice(b"hello");
+        raw.extend_from_slice(&[0x01, 0x00, 0x00, 0xff, 0xff]);
+        assert_eq!(decompress(&raw, "deflate", 1024).unwrap(), b"hello");
+    }
+
+    #[test]
+    fn test_unsupported_content_encoding() {
+        let mut request = create_request("application/json", "{}");
+        request
+            .headers
+            .insert(CONTENT_ENCODING, "compress".parse().unwrap());
+        let err = prepare_request_body(&mut request, &RequestBodyConfig::default()).unwrap_err();
+        assert!(format!("{:?}", err).contains("UnsupportedMediaTypeError"));
+    }
+}
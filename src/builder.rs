@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+
+use aws_lambda_events::http::HeaderValue;
+use fractic_server_error::ServerError;
+
+use crate::{
+    compression::ResponseCompression,
+    errors::InvalidRoutingConfigError,
+    routing::{AccessLevel, CrudRoute, FunctionRoute, RoutingConfig},
+};
+
+// Routing config builder.
+// --------------------------------------------------
+//
+// Builds an immutable RoutingConfig, for example:
+//
+//   let config = RoutingConfig::builder()
+//       .function("orders/create", create_order_route)
+//       .crud("orders", orders_crud_route)
+//       .build()?;
+//
+// Paths are normalized (leading, trailing and repeated slashes are removed),
+// and all problems (duplicate paths, paths registered as both a function and
+// a CRUD route, empty paths, unresolved AccessLevel::Inherit, invalid
+// Cache-Control values or an empty config) are reported together by build(),
+// so that they surface at cold start rather than as unexpected routing at
+// request time.
+
+#[derive(Default)]
+pub struct RoutingConfigBuilder {
+    function_routes: Vec<(String, FunctionRoute)>,
+    crud_routes: Vec<(String, CrudRoute)>,
+    compression: Option<ResponseCompression>,
+    // Path and docs of the route serving the OpenAPI document, if enabled.
+    #[cfg(feature = "openapi")]
+    pub(crate) openapi: Option<(String, crate::openapi::OpenApi)>,
+}
+
+impl RoutingConfig {
+    pub fn builder() -> RoutingConfigBuilder {
+        RoutingConfigBuilder::default()
+    }
+}
+
+impl RoutingConfigBuilder {
+    pub fn function(mut self, path: &str, route: FunctionRoute) -> Self {
+        self.function_routes.push((path.to_string(), route));
+        self
+    }
+
+    pub fn crud(mut self, path: &str, route: CrudRoute) -> Self {
+        self.crud_routes.push((path.to_string(), route));
+        self
+    }
+
+    // Compresses responses for clients which accept it (see
+    // ResponseCompression). Disabled by default.
+    pub fn compression(mut self, compression: ResponseCompression) -> Self {
+        self.compression = Some(compression);
+        self
+    }
+
+    pub fn build(self) -> Result<RoutingConfig, ServerError> {
+        let mut problems = Vec::new();
+        if self.function_routes.is_empty() && self.crud_routes.is_empty() {
+            problems.push("no routes were registered".to_string());
+        }
+
+        let mut function_routes = HashMap::new();
+        for (path, route) in self.function_routes {
+            let normalized = normalize_path(&path);
+            if normalized.is_empty() {
+                problems.push(format!("function route path '{}' is empty", path));
+            } else if route.access_level == AccessLevel::Inherit {
+                problems.push(inherit_problem(&normalized));
+            } else if function_routes.insert(normalized.clone(), route).is_some() {
+                problems.push(format!(
+                    "function route '{}' is registered more than once",
+                    normalized
+                ));
+            }
+        }
+
+        let mut crud_routes = HashMap::new();
+        for (path, route) in self.crud_routes {
+            let normalized = normalize_path(&path);
+            if normalized.is_empty() {
+                problems.push(format!("CRUD route path '{}' is empty", path));
+            } else if function_routes.contains_key(&normalized) {
+                problems.push(format!(
+                    "'{}' is registered as both a function route and a CRUD route",
+                    normalized
+                ));
+            } else if [
+                route.create_access_level,
+                route.read_access_level,
+                route.update_access_level,
+                route.delete_access_level,
+            ]
+            .contains(&AccessLevel::Inherit)
+            {
+                problems.push(inherit_problem(&normalized));
+            } else if route
+                .cache_control
+                .as_deref()
+                .is_some_and(|v| HeaderValue::from_str(v).is_err())
+            {
+                problems.push(format!(
+                    "CRUD route '{}' has an invalid Cache-Control value",
+                    normalized
+                ));
+            } else if crud_routes.insert(normalized.clone(), route).is_some() {
+                problems.push(format!(
+                    "CRUD route '{}' is registered more than once",
+                    normalized
+                ));
+            }
+        }
+
+        #[cfg_attr(not(feature = "openapi"), allow(unused_mut))]
+        let mut config = RoutingConfig {
+            function_routes,
+            crud_routes,
+            compression: self.compression,
+        };
+        #[cfg(feature = "openapi")]
+        if let (true, Some((path, openapi))) = (problems.is_empty(), &self.openapi) {
+            if let Err(openapi_problems) =
+                crate::openapi::add_openapi_route(&mut config, path, openapi)
+            {
+                problems.extend(openapi_problems);
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(config)
+        } else {
+            Err(InvalidRoutingConfigError::new(&problems.join("; ")))
+        }
+    }
+}
+
+// Helper functions.
+// --------------------------------------------------
+
+// Removes leading, trailing and repeated slashes, so that "/orders//create/"
+// and "orders/create" refer to the same route.
+pub(crate) fn normalize_path(path: &str) -> String {
+    path.split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn inherit_problem(path: &str) -> String {
+    format!(
+        "'{}' inherits its access level, but no enclosing group sets one",
+        path
+    )
+}
+
+// Tests.
+// --------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        routing::{box_route_handler, AccessLevel},
+        testing::{ok_handler, ok_route},
+    };
+
+    fn crud_route() -> CrudRoute {
+        CrudRoute {
+            create_access_level: AccessLevel::User,
+            read_access_level: AccessLevel::User,
+            update_access_level: AccessLevel::User,
+            delete_access_level: AccessLevel::User,
+            handler: box_route_handler(ok_handler),
+            timeout: None,
+            body_config: Default::default(),
+            field_selection: Default::default(),
+            cache_control: None,
+        }
+    }
+
+    #[test]
+    fn test_paths_normalized() {
+        let config = RoutingConfig::builder()
+            .function("/orders//create/", ok_route(AccessLevel::Guest))
+            .crud("items/", crud_route())
+            .build()
+            .unwrap();
+        assert!(config.function_routes().contains_key("orders/create"));
+        assert!(config.crud_routes().contains_key("items"));
+    }
+
+    #[test]
+    fn test_conflicts_rejected() {
+        let err = RoutingConfig::builder()
+            .function("orders", ok_route(AccessLevel::Guest))
+            .function("/orders/", ok_route(AccessLevel::Guest))
+            .crud("orders", crud_route())
+            .crud("/", crud_route())
+            .build()
+            .err()
+            .unwrap();
+        let msg = format!("{:?}", err);
+        assert!(msg.contains("function route 'orders' is registered more than once"));
+        assert!(msg.contains("'orders' is registered as both a function route and a CRUD route"));
+        assert!(msg.contains("CRUD route path '/' is empty"));
+    }
+
+    #[test]
+    fn test_empty_config_rejected() {
+        let err = RoutingConfig::builder().build().err().unwrap();
+        assert!(format!("{:?}", err).contains("no routes were registered"));
+    }
+}
@@ -0,0 +1,456 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use aws_lambda_events::{
+    apigw::{ApiGatewayProxyRequest, ApiGatewayProxyResponse},
+    encodings::Body,
+    http::{
+        header::{
+            CACHE_CONTROL, CONTENT_ENCODING, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH,
+            LAST_MODIFIED,
+        },
+        HeaderValue, Method,
+    },
+};
+use sha2::{Digest, Sha256};
+
+// HTTP caching.
+// --------------------------------------------------
+//
+// Successful GET responses (in practice, CRUD reads) get a strong ETag
+// computed over the final response body, unless the handler already set one
+// (see ApiResponse::with_etag). Conditional requests are answered with 304 Not
+// Modified and no body:
+//   - If-None-Match, compared against the ETag.
+//   - If-Modified-Since, compared against the Last-Modified header set by the
+//     handler (see ApiResponse::with_last_modified), if there is no
+//     If-None-Match.
+//
+// The handler still runs for conditional requests, so this saves bandwidth
+// for polling clients rather than work on the server.
+//
+// The Cache-Control header is configured per route (see
+// CrudRoute::cache_control). Error responses are sent with
+// 'Cache-Control: no-store', and never get an ETag.
+
+// Conditional request headers, captured before the request is dispatched.
+pub(crate) struct CacheConditions {
+    is_get: bool,
+    if_none_match: Option<String>,
+    if_modified_since: Option<String>,
+}
+
+impl CacheConditions {
+    pub(crate) fn from_request(request: &ApiGatewayProxyRequest) -> Self {
+        let header = |name| {
+            request
+                .headers
+                .get(name)
+                .and_then(|v: &HeaderValue| v.to_str().ok())
+                .map(|v| v.to_string())
+        };
+        CacheConditions {
+            is_get: request.http_method == Method::GET,
+            if_none_match: header(IF_NONE_MATCH),
+            if_modified_since: header(IF_MODIFIED_SINCE),
+        }
+    }
+}
+
+// Adds the ETag to the response, and replaces it with a 304 response if the
+// client's cached copy is still fresh. Should be applied to the final response
+// (after compression), since the ETag identifies the exact representation.
+pub(crate) fn apply_conditional_get(
+    response: &mut ApiGatewayProxyResponse,
+    conditions: &CacheConditions,
+) {
+    if !conditions.is_get || response.status_code != 200 {
+        return;
+    }
+    let no_store = response
+        .headers
+        .get(CACHE_CONTROL)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.contains("no-store"));
+    if no_store {
+        return;
+    }
+    if !response.headers.contains_key(ETAG) {
+        if let Some(etag) = response.body.as_ref().map(compute_etag) {
+            if let Ok(etag) = HeaderValue::from_str(&etag) {
+                response.headers.insert(ETAG, etag);
+            }
+        }
+    }
+    if is_not_modified(response, conditions) {
+        response.status_code = 304;
+        response.body = None;
+        response.is_base64_encoded = false;
+        response.headers.remove(CONTENT_TYPE);
+        response.headers.remove(CONTENT_ENCODING);
+    }
+}
+
+// Helper functions.
+// --------------------------------------------------
+
+fn is_not_modified(response: &ApiGatewayProxyResponse, conditions: &CacheConditions) -> bool {
+    let header = |name| {
+        response
+            .headers
+            .get(name)
+            .and_then(|v: &HeaderValue| v.to_str().ok())
+    };
+    // If-None-Match takes precedence over If-Modified-Since (RFC 9110, section
+    // 13.2.2), and uses the weak comparison.
+    if let Some(if_none_match) = &conditions.if_none_match {
+        let Some(etag) = header(ETAG) else {
+            return false;
+        };
+        return if_none_match
+            .split(',')
+            .map(str::trim)
+            .any(|tag| tag == "*" || opaque_tag(tag) == opaque_tag(etag));
+    }
+    match (
+        conditions
+            .if_modified_since
+            .as_deref()
+            .and_then(parse_http_date),
+        header(LAST_MODIFIED).and_then(parse_http_date),
+    ) {
+        (Some(since), Some(last_modified)) => last_modified <= since,
+        _ => false,
+    }
+}
+
+fn opaque_tag(tag: &str) -> &str {
+    tag.strip_prefix("W/").unwrap_or(tag)
+}
+
+// Strong ETag of the body (first 128 bits of its SHA-256 hash).
+fn compute_etag(body: &Body) -> String {
+    let bytes: &[u8] = match body {
+        Body::Empty => &[],
+        Body::Text(text) => text.as_bytes(),
+        Body::Binary(bytes) => bytes,
+    };
+    let hash: String = Sha256::digest(bytes)[..16]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    format!("\"{}\"", hash)
+}
+
+// Formats the time as an IMF-fixdate (e.g. 'Sun, 06 Nov 1994 08:49:37 GMT').
+// Times before the epoch are clamped to the epoch.
+pub(crate) fn http_date(time: SystemTime) -> String {
+    const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let days = secs / 86400;
+    let secs_of_day = secs % 86400;
+
+    // Civil date from days since the epoch (see Howard Hinnant's
+    // 'civil_from_days' algorithm).
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+        WEEKDAYS[(days % 7) as usize],
+        day,
+        MONTHS[(month - 1) as usize],
+        year,
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60
+    )
+}
+
+// Parses an IMF-fixdate, the only date format which servers must generate
+// (obsolete formats are not supported).
+pub(crate) fn parse_http_date(date: &str) -> Option<SystemTime> {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let (_weekday, rest) = date.trim().split_once(", ")?;
+    let parts: Vec<&str> = rest.split(' ').collect();
+    let [day, month, year, time, "GMT"] = parts.as_slice() else {
+        return None;
+    };
+    let month = MONTHS.iter().position(|m| m == month)? as u64 + 1;
+    let (hours, minutes, seconds) = parse_time(time)?;
+    to_system_time(
+        year.parse().ok()?,
+        month,
+        day.parse().ok()?,
+        hours,
+        minutes,
+        seconds,
+    )
+}
+
+// Parses a timestamp stored on an item, either as an RFC 3339 string (e.g.
+// '2024-06-01T12:00:00.123Z') or as a number of seconds or milliseconds since
+// the epoch. Sub-second precision is dropped, as in HTTP dates.
+pub(crate) fn parse_timestamp(value: &serde_json::Value) -> Option<SystemTime> {
+    if let Some(number) = value.as_u64() {
+        // Millisecond timestamps are distinguished by their magnitude (any
+        // time after 1973 in milliseconds exceeds 1e11).
+        let secs = match number >= 100_000_000_000 {
+            true => number / 1000,
+            false => number,
+        };
+        return Some(UNIX_EPOCH + Duration::from_secs(secs));
+    }
+    let text = value.as_str()?;
+    let (date, time) = text.split_once(['T', 't', ' '])?;
+    let mut date_parts = date.splitn(3, '-');
+    let year = date_parts.next()?.parse().ok()?;
+    let month = date_parts.next()?.parse().ok()?;
+    let day = date_parts.next()?.parse().ok()?;
+
+    // Split off the UTC offset ('Z' or '+HH:MM' / '-HH:MM').
+    let (time, offset_secs) = match time.strip_suffix(['Z', 'z']) {
+        Some(time) => (time, 0),
+        None => {
+            let split = time.rfind(['+', '-'])?;
+            let (time, offset) = time.split_at(split);
+            let (offset_hours, offset_minutes) = offset[1..].split_once(':')?;
+            let offset_secs =
+                offset_hours.parse::<i64>().ok()? * 3600 + offset_minutes.parse::<i64>().ok()? * 60;
+            match offset.starts_with('-') {
+                true => (time, -offset_secs),
+                false => (time, offset_secs),
+            }
+        }
+    };
+    let time = time.split('.').next()?;
+    let (hours, minutes, seconds) = parse_time(time)?;
+    let local = to_system_time(year, month, day, hours, minutes, seconds)?;
+    match offset_secs >= 0 {
+        true => local.checked_sub(Duration::from_secs(offset_secs as u64)),
+        false => local.checked_add(Duration::from_secs(offset_secs.unsigned_abs())),
+    }
+}
+
+fn parse_time(time: &str) -> Option<(u64, u64, u64)> {
+    let mut parts = time.splitn(3, ':');
+    let hours = parts.next()?.parse().ok().filter(|h| *h < 24)?;
+    let minutes = parts.next()?.parse().ok().filter(|m| *m < 60)?;
+    let seconds = parts.next()?.parse().ok().filter(|s| *s <= 60)?;
+    Some((hours, minutes, seconds))
+}
+
+fn to_system_time(
+    year: u64,
+    month: u64,
+    day: u64,
+    hours: u64,
+    minutes: u64,
+    seconds: u64,
+) -> Option<SystemTime> {
+    if year < 1970 || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    // Days since the epoch from the civil date (see Howard Hinnant's
+    // 'days_from_civil' algorithm).
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y / 400;
+    let yoe = y - era * 400;
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = (era * 146097 + doe).checked_sub(719468)?;
+    Some(UNIX_EPOCH + Duration::from_secs(days * 86400 + hours * 3600 + minutes * 60 + seconds))
+}
+
+// Tests.
+// --------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn create_response(body: &str) -> ApiGatewayProxyResponse {
+        ApiGatewayProxyResponse {
+            status_code: 200,
+            body: Some(Body::Text(body.to_string())),
+            ..Default::default()
+        }
+    }
+
+    fn create_conditions(
+        if_none_match: Option<&str>,
+        if_modified_since: Option<&str>,
+    ) -> CacheConditions {
+        CacheConditions {
+            is_get: true,
+            if_none_match: if_none_match.map(|v| v.to_string()),
+            if_modified_since: if_modified_since.map(|v| v.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_http_date() {
+        assert_eq!(http_date(UNIX_EPOCH), "Thu, 01 Jan 1970 00:00:00 GMT");
+        let date = UNIX_EPOCH + Duration::from_secs(784111777);
+        assert_eq!(http_date(date), "Sun, 06 Nov 1994 08:49:37 GMT");
+        assert_eq!(parse_http_date(&http_date(date)), Some(date));
+        let leap_day = UNIX_EPOCH + Duration::from_secs(951782400);
+        assert_eq!(http_date(leap_day), "Tue, 29 Feb 2000 00:00:00 GMT");
+        assert_eq!(parse_http_date(&http_date(leap_day)), Some(leap_day));
+        assert_eq!(parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT"), None);
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        let expected = Some(UNIX_EPOCH + Duration::from_secs(784111777));
+        assert_eq!(parse_timestamp(&json!("1994-11-06T08:49:37Z")), expected);
+        assert_eq!(
+            parse_timestamp(&json!("1994-11-06T08:49:37.250Z")),
+            expected
+        );
+        assert_eq!(
+            parse_timestamp(&json!("1994-11-06T10:49:37+02:00")),
+            expected
+        );
+        assert_eq!(parse_timestamp(&json!(784111777)), expected);
+        assert_eq!(parse_timestamp(&json!(784111777250u64)), expected);
+        assert_eq!(parse_timestamp(&json!("yesterday")), None);
+    }
+
+    #[test]
+    fn test_etag_and_if_none_match() {
+        let mut response = create_response("{\"ok\":true}");
+        apply_conditional_get(&mut response, &create_conditions(None, None));
+        assert_eq!(response.status_code, 200);
+        let etag = response.headers[ETAG].to_str().unwrap().to_string();
+        assert!(etag.starts_with('"') && etag.len() == 34);
+
+        // Matching tags (including weak ones) are not modified.
+        for if_none_match in [etag.clone(), format!("\"other\", W/{}", etag), "*".into()] {
+            let mut response = create_response("{\"ok\":true}");
+            apply_conditional_get(
+                &mut response,
+                &create_conditions(Some(&if_none_match), None),
+            );
+            assert_eq!(response.status_code, 304);
+            assert!(response.body.is_none());
+            assert_eq!(response.headers[ETAG], etag.as_str());
+        }
+
+        let mut response = create_response("{\"ok\":false}");
+        apply_conditional_get(&mut response, &create_conditions(Some(&etag), None));
+        assert_eq!(response.status_code, 200);
+    }
+
+    #[test]
+    fn test_if_modified_since() {
+        let last_modified = "Sun, 06 Nov 1994 08:49:37 GMT";
+        let check = |if_modified_since: &str| {
+            let mut response = create_response("{}");
+            response
+                .headers
+                .insert(LAST_MODIFIED, HeaderValue::from_static(last_modified));
+            apply_conditional_get(
+                &mut response,
+                &create_conditions(None, Some(if_modified_since)),
+            );
+            response.status_code
+        };
+        assert_eq!(check(last_modified), 304);
+        assert_eq!(check("Mon, 07 Nov 1994 00:00:00 GMT"), 304);
+        assert_eq!(check("Sat, 05 Nov 1994 00:00:00 GMT"), 200);
+        assert_eq!(check("invalid"), 200);
+    }
+
+    #[tokio::test]
+    async fn test_routed_conditional_get() {
+        use crate::{
+            request::RequestMetadata,
+            routing::{box_route_handler, AccessLevel, CrudRoute, RoutingConfig},
+            testing::{TestClient, TestRequest},
+        };
+        use lambda_runtime::{Error, LambdaEvent};
+
+        async fn items(
+            event: LambdaEvent<ApiGatewayProxyRequest>,
+            _: RequestMetadata,
+        ) -> Result<ApiGatewayProxyResponse, Error> {
+            match event.payload.query_string_parameters.first("id") {
+                Some(id) => crate::build_result(id),
+                None => crate::build_error(crate::ValidationError::new("id is required")),
+            }
+        }
+
+        let client = TestClient::new(
+            RoutingConfig::builder()
+                .crud(
+                    "items",
+                    CrudRoute {
+                        create_access_level: AccessLevel::None,
+                        read_access_level: AccessLevel::Guest,
+                        update_access_level: AccessLevel::None,
+                        delete_access_level: AccessLevel::None,
+                        handler: box_route_handler(items),
+                        timeout: None,
+                        body_config: Default::default(),
+                        field_selection: Default::default(),
+                        cache_control: Some("private, max-age=60".to_string()),
+                    },
+                )
+                .build()
+                .unwrap(),
+        );
+
+        let response = client
+            .send(TestRequest::get("items").query("id", "1"))
+            .await;
+        assert_eq!(
+            response.header("cache-control"),
+            Some("private, max-age=60")
+        );
+        let etag = response.header("etag").unwrap().to_string();
+        let response = client
+            .send(
+                TestRequest::get("items")
+                    .query("id", "1")
+                    .header("If-None-Match", &etag),
+            )
+            .await;
+        response.assert_status(304);
+        assert_eq!(response.header("etag"), Some(etag.as_str()));
+
+        // Errors are never cached.
+        let response = client.send(TestRequest::get("items")).await;
+        assert_eq!(response.header("cache-control"), Some("no-store"));
+        assert!(response.header("etag").is_none());
+    }
+
+    #[test]
+    fn test_no_store_not_cached() {
+        let mut response = create_response("{}");
+        response
+            .headers
+            .insert(CACHE_CONTROL, HeaderValue::from_static("no-store"));
+        apply_conditional_get(&mut response, &create_conditions(Some("*"), None));
+        assert_eq!(response.status_code, 200);
+        assert!(!response.headers.contains_key(ETAG));
+    }
+}
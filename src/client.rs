@@ -0,0 +1,455 @@
+use std::{fmt, future::Future, marker::PhantomData, pin::Pin, sync::Arc};
+
+use aws_lambda_events::{
+    encodings::Body,
+    http::{
+        header::{AUTHORIZATION, CONTENT_TYPE},
+        HeaderValue, Method, Request, Response,
+    },
+};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use tower_service::Service;
+
+use crate::{correlation::REQUEST_ID_HEADER, response::ResponseWrapper, service::Router};
+
+// API client.
+// --------------------------------------------------
+//
+// Calls routes of an API built with this crate, for example from another
+// service or a CLI tool:
+//
+//   // Shared between the server and its clients:
+//   pub const CREATE_ORDER: FunctionEndpoint<CreateOrder, Order> =
+//       FunctionEndpoint::new("orders/create");
+//
+//   let client = ApiClient::new("https://api.example.com/prod", transport)
+//       .with_signer(TokenAuth::cognito(&token));
+//   let order = client.call(&CREATE_ORDER, &request).await?;
+//
+// Responses are unwrapped from the ResponseWrapper envelope, with failures
+// reported as ApiError. Both the transport (which sends the http::Request) and
+// the signer (which authenticates it, for example with a token or SigV4) are
+// pluggable. Router implements HttpTransport, so the client can also call a
+// RoutingConfig in-process. For calls over the network, see ReqwestTransport
+// (http_client.rs).
+
+pub type TransportFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<Response<Body>, ApiError>> + Send + 'a>>;
+
+pub type SignerFuture<'a> = Pin<Box<dyn Future<Output = Result<(), ApiError>> + Send + 'a>>;
+
+pub trait HttpTransport: Send + Sync {
+    fn send(&self, request: Request<Body>) -> TransportFuture<'_>;
+}
+
+// Adds authentication to outgoing requests. Signing is async, so that
+// implementations can refresh credentials.
+pub trait RequestSigner: Send + Sync {
+    fn sign<'a>(&'a self, request: &'a mut Request<Body>) -> SignerFuture<'a>;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApiError {
+    // Error forwarded to the client by the route (response with ok == false).
+    // The message is safe to show to the user.
+    Api {
+        status: u16,
+        message: String,
+        request_id: Option<String>,
+    },
+    // Non-2xx response which is not wrapped (for example 401 or 500).
+    Http {
+        status: u16,
+        body: String,
+        request_id: Option<String>,
+    },
+    Auth(String),
+    // The request could not be built (for example if the data cannot be
+    // serialized).
+    Encode(String),
+    Transport(String),
+    Decode(String),
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::Api {
+                status, message, ..
+            } => write!(f, "API error ({}): {}", status, message),
+            ApiError::Http { status, body, .. } => write!(f, "HTTP error ({}): {}", status, body),
+            ApiError::Auth(e) => write!(f, "failed to authenticate request: {}", e),
+            ApiError::Encode(e) => write!(f, "failed to encode request: {}", e),
+            ApiError::Transport(e) => write!(f, "failed to send request: {}", e),
+            ApiError::Decode(e) => write!(f, "failed to decode response: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl ApiError {
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            ApiError::Api { status, .. } | ApiError::Http { status, .. } => Some(*status),
+            _ => None,
+        }
+    }
+
+    pub fn request_id(&self) -> Option<&str> {
+        match self {
+            ApiError::Api { request_id, .. } | ApiError::Http { request_id, .. } => {
+                request_id.as_deref()
+            }
+            _ => None,
+        }
+    }
+}
+
+// Function route with its request and response data types.
+pub struct FunctionEndpoint<Req, Res> {
+    pub path: &'static str,
+    _types: PhantomData<fn(Req) -> Res>,
+}
+
+impl<Req, Res> FunctionEndpoint<Req, Res> {
+    pub const fn new(path: &'static str) -> Self {
+        FunctionEndpoint {
+            path,
+            _types: PhantomData,
+        }
+    }
+}
+
+// Sets the Authorization header to a fixed token.
+pub struct TokenAuth {
+    header_value: String,
+}
+
+impl TokenAuth {
+    // "Bearer <token>".
+    pub fn bearer(token: &str) -> Self {
+        TokenAuth {
+            header_value: format!("Bearer {}", token),
+        }
+    }
+
+    // The bare token, as expected by API Gateway's Cognito authorizer.
+    pub fn cognito(token: &str) -> Self {
+        TokenAuth {
+            header_value: token.to_string(),
+        }
+    }
+}
+
+impl RequestSigner for TokenAuth {
+    fn sign<'a>(&'a self, request: &'a mut Request<Body>) -> SignerFuture<'a> {
+        Box::pin(async move {
+            let value = HeaderValue::from_str(&self.header_value)
+                .map_err(|e| ApiError::Auth(e.to_string()))?;
+            request.headers_mut().insert(AUTHORIZATION, value);
+            Ok(())
+        })
+    }
+}
+
+impl HttpTransport for Router {
+    fn send(&self, request: Request<Body>) -> TransportFuture<'_> {
+        let mut router = self.clone();
+        Box::pin(async move {
+            router
+                .call(request)
+                .await
+                .map_err(|e| ApiError::Transport(e.to_string()))
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct ApiClient {
+    base_url: String,
+    transport: Arc<dyn HttpTransport>,
+    signer: Option<Arc<dyn RequestSigner>>,
+}
+
+impl ApiClient {
+    // For in-process transports (such as Router), the base URL can be empty.
+    pub fn new(base_url: &str, transport: impl HttpTransport + 'static) -> Self {
+        ApiClient {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            transport: Arc::new(transport),
+            signer: None,
+        }
+    }
+
+    pub fn with_signer(mut self, signer: impl RequestSigner + 'static) -> Self {
+        self.signer = Some(Arc::new(signer));
+        self
+    }
+
+    // Calls a function route.
+    pub async fn call<Req, Res>(
+        &self,
+        endpoint: &FunctionEndpoint<Req, Res>,
+        data: &Req,
+    ) -> Result<Res, ApiError>
+    where
+        Req: Serialize,
+        Res: DeserializeOwned,
+    {
+        self.send(Method::POST, endpoint.path, &[], Some(data))
+            .await
+    }
+
+    // CRUD route calls, using the query parameters of CrudRouteScaffolding.
+
+    pub async fn create<D, Res>(
+        &self,
+        path: &str,
+        parent_id: &str,
+        data: &D,
+    ) -> Result<Res, ApiError>
+    where
+        D: Serialize,
+        Res: DeserializeOwned,
+    {
+        self.send(Method::POST, path, &[("parent_id", parent_id)], Some(data))
+            .await
+    }
+
+    pub async fn read<Res: DeserializeOwned>(&self, path: &str, id: &str) -> Result<Res, ApiError> {
+        self.send::<(), _>(Method::GET, path, &[("id", id)], None)
+            .await
+    }
+
+    pub async fn update<T: Serialize>(&self, path: &str, object: &T) -> Result<(), ApiError> {
+        self.send(Method::PUT, path, &[], Some(object)).await
+    }
+
+    pub async fn delete(&self, path: &str, id: &str) -> Result<(), ApiError> {
+        self.send::<(), _>(Method::DELETE, path, &[("id", id)], None)
+            .await
+    }
+
+    // Sends a request to any route, unwrapping the response.
+    pub async fn send<D, Res>(
+        &self,
+        method: Method,
+        path: &str,
+        query: &[(&str, &str)],
+        data: Option<&D>,
+    ) -> Result<Res, ApiError>
+    where
+        D: Serialize,
+        Res: DeserializeOwned,
+    {
+        let mut uri = format!("{}/{}", self.base_url, path.trim_start_matches('/'));
+        if !query.is_empty() {
+            uri.push('?');
+            uri.push_str(
+                &form_urlencoded::Serializer::new(String::new())
+                    .extend_pairs(query)
+                    .finish(),
+            );
+        }
+        let mut builder = Request::builder().method(method).uri(uri);
+        let body = match data {
+            Some(data) => {
+                builder = builder.header(CONTENT_TYPE, "application/json");
+                Body::Text(
+                    serde_json::to_string(data).map_err(|e| ApiError::Encode(e.to_string()))?,
+                )
+            }
+            None => Body::Empty,
+        };
+        let mut request = builder
+            .body(body)
+            .map_err(|e| ApiError::Encode(e.to_string()))?;
+        if let Some(signer) = &self.signer {
+            signer.sign(&mut request).await?;
+        }
+
+        let response = self.transport.send(request).await?;
+        let status = response.status().as_u16();
+        let request_id = response
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string());
+        let body = String::from_utf8_lossy(response.body()).into_owned();
+        if !(200..300).contains(&status) {
+            return Err(ApiError::Http {
+                status,
+                body,
+                request_id,
+            });
+        }
+        // Routes may respond without a body (for example 204 No Content).
+        if body.is_empty() {
+            return serde_json::from_value(Value::Null)
+                .map_err(|e| ApiError::Decode(e.to_string()));
+        }
+        let wrapper: ResponseWrapper<Value> =
+            serde_json::from_str(&body).map_err(|e| ApiError::Decode(e.to_string()))?;
+        if !wrapper.ok {
+            return Err(ApiError::Api {
+                status,
+                message: wrapper.error.unwrap_or_default(),
+                request_id: wrapper.request_id.or(request_id),
+            });
+        }
+        // Routes without response data (for example CRUD updates) send null.
+        serde_json::from_value(wrapper.data.unwrap_or_default())
+            .map_err(|e| ApiError::Decode(e.to_string()))
+    }
+}
+
+// Tests.
+// --------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        errors::ValidationError,
+        request::RequestMetadata,
+        response::{ApiResponse, IntoApiResponse},
+        routing::{box_route_handler, AccessLevel, CrudRoute, FunctionRoute, RoutingConfig},
+        testing::FakeIdentity,
+    };
+    use aws_lambda_events::apigw::{ApiGatewayProxyRequest, ApiGatewayProxyResponse};
+    use lambda_runtime::{Error, LambdaEvent};
+    use serde::Deserialize;
+
+    #[derive(Serialize, Deserialize)]
+    struct Greeting {
+        name: String,
+    }
+
+    const GREET: FunctionEndpoint<Greeting, String> = FunctionEndpoint::new("greet");
+    const WHOAMI: FunctionEndpoint<(), Option<String>> = FunctionEndpoint::new("whoami");
+
+    async fn greet(
+        event: LambdaEvent<ApiGatewayProxyRequest>,
+        _: RequestMetadata,
+    ) -> Result<ApiGatewayProxyResponse, Error> {
+        match crate::parse_request_data::<Greeting>(&event.payload) {
+            Ok(greeting) if !greeting.name.is_empty() => {
+                crate::build_result(format!("Hello, {}!", greeting.name))
+            }
+            _ => crate::build_error(ValidationError::new("name is required")),
+        }
+    }
+
+    async fn whoami(
+        _: LambdaEvent<ApiGatewayProxyRequest>,
+        metadata: RequestMetadata,
+    ) -> Result<ApiGatewayProxyResponse, Error> {
+        crate::build_result(metadata.user_sub)
+    }
+
+    async fn items(
+        event: LambdaEvent<ApiGatewayProxyRequest>,
+        _: RequestMetadata,
+    ) -> Result<ApiGatewayProxyResponse, Error> {
+        let query = &event.payload.query_string_parameters;
+        match event.payload.http_method {
+            Method::GET => crate::build_result(query.first("id")),
+            Method::POST => crate::build_result(query.first("parent_id")),
+            Method::DELETE => ApiResponse::no_content().into_api_response(),
+            _ => crate::build_result(()),
+        }
+    }
+
+    fn create_client() -> ApiClient {
+        let config = RoutingConfig::builder()
+            .function(
+                "greet",
+                FunctionRoute::new(AccessLevel::Guest, box_route_handler(greet)),
+            )
+            .function(
+                "whoami",
+                FunctionRoute::new(AccessLevel::User, box_route_handler(whoami)),
+            )
+            .crud(
+                "items",
+                CrudRoute {
+                    create_access_level: AccessLevel::Guest,
+                    read_access_level: AccessLevel::Guest,
+                    update_access_level: AccessLevel::Guest,
+                    delete_access_level: AccessLevel::Guest,
+                    handler: box_route_handler(items),
+                    timeout: None,
+                    body_config: Default::default(),
+                    field_selection: Default::default(),
+                    cache_control: None,
+                },
+            )
+            .build()
+            .unwrap();
+        ApiClient::new("", Router::new(config))
+    }
+
+    #[tokio::test]
+    async fn test_call() {
+        let client = create_client();
+        let greeting = Greeting {
+            name: "Jane".to_string(),
+        };
+        assert_eq!(
+            client.call(&GREET, &greeting).await.unwrap(),
+            "Hello, Jane!"
+        );
+
+        let err = client
+            .call(&GREET, &Greeting { name: "".into() })
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&err, ApiError::Api { message, .. } if message.contains("name is required"))
+        );
+        assert_eq!(err.status(), Some(200));
+    }
+
+    #[tokio::test]
+    async fn test_encode_error() {
+        // JSON object keys must be strings.
+        let data = std::collections::HashMap::from([((1, 2), "value")]);
+        let err = create_client()
+            .send::<_, Value>(Method::POST, "greet", &[], Some(&data))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ApiError::Encode(_)));
+    }
+
+    #[tokio::test]
+    async fn test_auth() {
+        let client = create_client();
+        let err = client.call(&WHOAMI, &()).await.unwrap_err();
+        assert_eq!(err.status(), Some(401));
+
+        let client = client.with_signer(FakeIdentity::user("sub-1"));
+        assert_eq!(
+            client.call(&WHOAMI, &()).await.unwrap(),
+            Some("sub-1".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_crud() {
+        let client = create_client();
+        let id: String = client.read("items", "ITEM#1").await.unwrap();
+        assert_eq!(id, "ITEM#1");
+        let parent_id: String = client
+            .create("/items/", "LIST#1", &Greeting { name: "x".into() })
+            .await
+            .unwrap();
+        assert_eq!(parent_id, "LIST#1");
+        client
+            .update("items", &Greeting { name: "x".into() })
+            .await
+            .unwrap();
+        client.delete("items", "ITEM#1").await.unwrap();
+    }
+}
@@ -0,0 +1,336 @@
+use std::io::Write;
+
+use aws_lambda_events::{
+    apigw::ApiGatewayProxyResponse,
+    encodings::Body,
+    http::{
+        header::{CONTENT_ENCODING, ETAG, VARY},
+        HeaderValue,
+    },
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use flate2::{write::GzEncoder, Compression};
+
+// Response compression config.
+// --------------------------------------------------
+//
+// Enabled with RoutingConfigBuilder::compression. Responses with a body of at
+// least min_size bytes are compressed with the best encoding accepted by the
+// client (per the request's Accept-Encoding header), and returned base64-encoded
+// with is_base64_encoded set.
+//
+// NOTE: API Gateway only decodes base64 response bodies if the API has binary
+// media types configured ('*/*'). SamTemplate does this automatically for
+// configs with compression enabled.
+
+#[derive(Debug, Clone)]
+pub struct ResponseCompression {
+    pub min_size: usize,
+    pub brotli: bool,
+    pub gzip: bool,
+}
+
+impl Default for ResponseCompression {
+    fn default() -> Self {
+        ResponseCompression {
+            min_size: 1024,
+            brotli: true,
+            gzip: true,
+        }
+    }
+}
+
+impl ResponseCompression {
+    pub fn with_min_size(mut self, min_size: usize) -> Self {
+        self.min_size = min_size;
+        self
+    }
+
+    pub fn without_brotli(mut self) -> Self {
+        self.brotli = false;
+        self
+    }
+
+    pub fn without_gzip(mut self) -> Self {
+        self.gzip = false;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Encoding {
+    Brotli,
+    Gzip,
+}
+
+impl Encoding {
+    fn name(self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Gzip => "gzip",
+        }
+    }
+}
+
+// Brotli quality (0-11). Higher levels compress slightly better, but are much
+// slower, which matters on small Lambda instances.
+const BROTLI_QUALITY: u32 = 5;
+const BROTLI_WINDOW_SIZE: u32 = 22;
+
+// Response compression utils.
+// --------------------------------------------------
+
+// Compresses the response in place, if it is large enough and the client
+// accepts a supported encoding. Responses which are already encoded or binary
+// are left as-is.
+pub(crate) fn compress_response(
+    response: &mut ApiGatewayProxyResponse,
+    accept_encoding: Option<&str>,
+    config: &ResponseCompression,
+) {
+    if response.is_base64_encoded || response.headers.contains_key(CONTENT_ENCODING) {
+        return;
+    }
+    let bytes = match &response.body {
+        Some(Body::Text(text)) if text.len() >= config.min_size => text.as_bytes(),
+        _ => return,
+    };
+
+    // The representation depends on Accept-Encoding from here on, even if this
+    // particular client does not accept compression.
+    response
+        .headers
+        .append(VARY, HeaderValue::from_static("Accept-Encoding"));
+    let Some(encoding) = accept_encoding.and_then(|header| negotiate_encoding(header, config))
+    else {
+        return;
+    };
+    let compressed = match encode(bytes, encoding) {
+        Ok(compressed) => compressed,
+        Err(e) => {
+            tracing::warn!("Failed to compress response, sending uncompressed: {}", e);
+            return;
+        }
+    };
+    response.body = Some(Body::Text(BASE64.encode(compressed)));
+    response.is_base64_encoded = true;
+    response
+        .headers
+        .insert(CONTENT_ENCODING, HeaderValue::from_static(encoding.name()));
+
+    // A strong ETag set by the handler identifies the uncompressed body, so
+    // needs to be made specific to the encoding.
+    let etag = response.headers.get(ETAG).and_then(|v| v.to_str().ok());
+    if let Some(tag) = etag.and_then(|v| v.strip_prefix('"')?.strip_suffix('"')) {
+        if let Ok(etag) = HeaderValue::from_str(&format!("\"{}-{}\"", tag, encoding.name())) {
+            response.headers.insert(ETAG, etag);
+        }
+    }
+}
+
+// Helper functions.
+// --------------------------------------------------
+
+// Picks the supported encoding with the highest quality value in the
+// Accept-Encoding header, preferring brotli over gzip on ties.
+fn negotiate_encoding(accept_encoding: &str, config: &ResponseCompression) -> Option<Encoding> {
+    let mut best: Option<(Encoding, f32)> = None;
+    let mut wildcard_q = None;
+    let mut explicit = Vec::new();
+    for item in accept_encoding.split(',') {
+        let mut parts = item.split(';');
+        let name = parts.next().unwrap_or_default().trim().to_ascii_lowercase();
+        let q = parts
+            .filter_map(|p| p.trim().strip_prefix("q="))
+            .find_map(|q| q.trim().parse::<f32>().ok())
+            .unwrap_or(1.0);
+        let encoding = match name.as_str() {
+            "br" => Encoding::Brotli,
+            "gzip" | "x-gzip" => Encoding::Gzip,
+            "*" => {
+                wildcard_q = Some(q);
+                continue;
+            }
+            _ => continue,
+        };
+        explicit.push(encoding);
+        consider(&mut best, encoding, q, config);
+    }
+    if let Some(q) = wildcard_q {
+        for encoding in [Encoding::Brotli, Encoding::Gzip] {
+            if !explicit.contains(&encoding) {
+                consider(&mut best, encoding, q, config);
+            }
+        }
+    }
+    best.map(|(encoding, _)| encoding)
+}
+
+fn consider(
+    best: &mut Option<(Encoding, f32)>,
+    encoding: Encoding,
+    q: f32,
+    config: &ResponseCompression,
+) {
+    let enabled = match encoding {
+        Encoding::Brotli => config.brotli,
+        Encoding::Gzip => config.gzip,
+    };
+    if !enabled || q <= 0.0 {
+        return;
+    }
+    let is_better = match best {
+        None => true,
+        Some((best_encoding, best_q)) => {
+            q > *best_q
+                || (q == *best_q && encoding == Encoding::Brotli && *best_encoding != encoding)
+        }
+    };
+    if is_better {
+        *best = Some((encoding, q));
+    }
+}
+
+fn encode(bytes: &[u8], encoding: Encoding) -> std::io::Result<Vec<u8>> {
+    match encoding {
+        Encoding::Gzip => {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(bytes)?;
+            encoder.finish()
+        }
+        Encoding::Brotli => {
+            let mut compressed = Vec::new();
+            {
+                let mut writer = brotli::CompressorWriter::new(
+                    &mut compressed,
+                    4096,
+                    BROTLI_QUALITY,
+                    BROTLI_WINDOW_SIZE,
+                );
+                writer.write_all(bytes)?;
+            }
+            Ok(compressed)
+        }
+    }
+}
+
+// Tests.
+// --------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    fn create_response(body: &str) -> ApiGatewayProxyResponse {
+        ApiGatewayProxyResponse {
+            status_code: 200,
+            body: Some(Body::Text(body.to_string())),
+            ..Default::default()
+        }
+    }
+
+    fn decoded_body(response: &ApiGatewayProxyResponse) -> Vec<u8> {
+        match &response.body {
+            Some(Body::Text(text)) => BASE64.decode(text).unwrap(),
+            _ => panic!("expected text body"),
+        }
+    }
+
+    #[test]
+    fn test_negotiate_encoding() {
+        let config = ResponseCompression::default();
+        assert_eq!(
+            negotiate_encoding("gzip, deflate, br", &config),
+            Some(Encoding::Brotli)
+        );
+        assert_eq!(
+            negotiate_encoding("br;q=0.5, gzip", &config),
+            Some(Encoding::Gzip)
+        );
+        assert_eq!(negotiate_encoding("gzip;q=0, deflate", &config), None);
+        assert_eq!(negotiate_encoding("*", &config), Some(Encoding::Brotli));
+        assert_eq!(
+            negotiate_encoding("br", &config.clone().without_brotli()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_compress_gzip() {
+        let body = "{\"ok\":true}".repeat(200);
+        let mut response = create_response(&body);
+        compress_response(&mut response, Some("gzip"), &ResponseCompression::default());
+        assert!(response.is_base64_encoded);
+        assert_eq!(response.headers[CONTENT_ENCODING], "gzip");
+        assert_eq!(response.headers[VARY], "Accept-Encoding");
+        let mut decompressed = String::new();
+        GzDecoder::new(decoded_body(&response).as_slice())
+            .read_to_string(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, body);
+    }
+
+    #[test]
+    fn test_compress_brotli() {
+        let body = "{\"ok\":true}".repeat(200);
+        let mut response = create_response(&body);
+        response
+            .headers
+            .insert(ETAG, HeaderValue::from_static("\"v1\""));
+        compress_response(&mut response, Some("br"), &ResponseCompression::default());
+        assert_eq!(response.headers[CONTENT_ENCODING], "br");
+        assert_eq!(response.headers[ETAG], "\"v1-br\"");
+        let mut decompressed = String::new();
+        brotli::Decompressor::new(decoded_body(&response).as_slice(), 4096)
+            .read_to_string(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, body);
+    }
+
+    #[tokio::test]
+    async fn test_routed_response_compressed() {
+        use crate::{
+            request::RequestMetadata,
+            routing::{box_route_handler, AccessLevel, FunctionRoute, RoutingConfig},
+            testing::{TestClient, TestRequest},
+        };
+        use aws_lambda_events::apigw::ApiGatewayProxyRequest;
+        use lambda_runtime::{Error, LambdaEvent};
+
+        async fn list_handler(
+            _: LambdaEvent<ApiGatewayProxyRequest>,
+            _: RequestMetadata,
+        ) -> Result<ApiGatewayProxyResponse, Error> {
+            crate::build_result(vec!["item"; 1000])
+        }
+
+        let config = RoutingConfig::builder()
+            .function(
+                "list",
+                FunctionRoute::new(AccessLevel::Guest, box_route_handler(list_handler)),
+            )
+            .compression(ResponseCompression::default())
+            .build()
+            .unwrap();
+        let response = TestClient::new(config)
+            .send(TestRequest::post("list").header("Accept-Encoding", "gzip, br"))
+            .await;
+        assert_eq!(response.header("content-encoding"), Some("br"));
+        assert!(response.response.is_base64_encoded);
+    }
+
+    #[test]
+    fn test_small_or_unaccepted_not_compressed() {
+        let mut response = create_response("{\"ok\":true}");
+        compress_response(&mut response, Some("gzip"), &ResponseCompression::default());
+        assert!(!response.is_base64_encoded);
+        assert!(!response.headers.contains_key(VARY));
+
+        let mut response = create_response(&"x".repeat(2048));
+        compress_response(&mut response, None, &ResponseCompression::default());
+        assert!(!response.is_base64_encoded);
+        assert_eq!(response.headers[VARY], "Accept-Encoding");
+    }
+}
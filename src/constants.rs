@@ -2,3 +2,14 @@ pub(crate) const INTERNAL_SERVER_ERROR_MSG: &str =
     "Unfortunately, an unexpected server error occurred. Please try updating to the latest version.\n\nIf the error persists, please contact the developer. We are sorry for the inconvenience. :(";
 pub(crate) const UNAUTHORIZED_ERROR_MSG: &str =
     "Unfortunately, the request was not properly authenticated. Please ensure you are logged in with a valid account, have access to the requested resource, and are using the latest version of the application.\n\nIf the error persists, please contact the developer. We are sorry for the inconvenience. :(";
+
+// CORS configuration of the API, used both for the response headers (see
+// build_headers) and for the preflight handling configured on the
+// AWS::Serverless::Api resource (see SamTemplate).
+pub(crate) const CORS_ALLOW_ORIGIN: &str = "https://fractic.io";
+pub(crate) const CORS_ALLOW_HEADERS: &str =
+    "Content-Type,X-Amz-Date,Authorization,X-Api-Key,X-Amz-Security-Token,X-Amz-User-Agent";
+pub(crate) const CORS_ALLOW_METHODS: &str = "GET, POST, PUT, DELETE";
+pub(crate) const CORS_ALLOW_CREDENTIALS: bool = true;
+pub(crate) const CORS_EXPOSE_HEADERS: &str = "X-Request-Id,X-Correlation-Id";
+pub(crate) const CORS_MAX_AGE_SECS: u32 = 600;
@@ -0,0 +1,205 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::caching::http_date;
+
+// Response cookies.
+// --------------------------------------------------
+//
+// Cookies set by a route through ApiResponse::with_cookie, for example:
+//
+//   ApiResponse::ok(session).with_cookie(
+//       Cookie::new("session", &token).with_max_age(Duration::from_secs(3600)),
+//   )
+//
+// New cookies default to Path=/, Secure, HttpOnly and SameSite=Lax, which can
+// be relaxed with the builder methods below.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SameSite {
+    Strict,
+    Lax,
+    // Requires Secure.
+    None,
+}
+
+#[derive(Debug, Clone)]
+pub struct Cookie {
+    pub name: String,
+    pub value: String,
+    pub path: Option<String>,
+    pub domain: Option<String>,
+    pub max_age: Option<Duration>,
+    pub expires: Option<SystemTime>,
+    pub same_site: Option<SameSite>,
+    pub secure: bool,
+    pub http_only: bool,
+}
+
+impl Cookie {
+    pub fn new(name: &str, value: &str) -> Self {
+        Cookie {
+            name: name.to_string(),
+            value: value.to_string(),
+            path: Some("/".to_string()),
+            domain: None,
+            max_age: None,
+            expires: None,
+            same_site: Some(SameSite::Lax),
+            secure: true,
+            http_only: true,
+        }
+    }
+
+    // Instructs the client to delete the cookie. The path and domain should
+    // match those the cookie was set with.
+    pub fn removal(name: &str) -> Self {
+        Cookie::new(name, "")
+            .with_max_age(Duration::ZERO)
+            .with_expires(UNIX_EPOCH)
+    }
+
+    pub fn with_path(mut self, path: &str) -> Self {
+        self.path = Some(path.to_string());
+        self
+    }
+
+    pub fn with_domain(mut self, domain: &str) -> Self {
+        self.domain = Some(domain.to_string());
+        self
+    }
+
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    // Absolute expiry time. Prefer with_max_age, which is not affected by the
+    // client's clock (if both are set, Max-Age takes precedence).
+    pub fn with_expires(mut self, expires: SystemTime) -> Self {
+        self.expires = Some(expires);
+        self
+    }
+
+    pub fn with_same_site(mut self, same_site: SameSite) -> Self {
+        self.same_site = Some(same_site);
+        self
+    }
+
+    pub fn without_same_site(mut self) -> Self {
+        self.same_site = None;
+        self
+    }
+
+    pub fn without_secure(mut self) -> Self {
+        self.secure = false;
+        self
+    }
+
+    pub fn without_http_only(mut self) -> Self {
+        self.http_only = false;
+        self
+    }
+
+    // Builds the Set-Cookie header value, failing if the cookie is invalid.
+    pub(crate) fn to_header_value(&self) -> Result<String, String> {
+        let mut problems = Vec::new();
+        if self.name.is_empty() || !self.name.bytes().all(is_token_char) {
+            problems.push(format!("invalid cookie name '{}'", self.name));
+        }
+        if !self.value.bytes().all(is_cookie_value_char) {
+            problems.push(format!("invalid value for cookie '{}'", self.name));
+        }
+        for attribute in [&self.path, &self.domain].into_iter().flatten() {
+            if attribute.bytes().any(|b| b == b';' || b.is_ascii_control()) {
+                problems.push(format!("invalid attribute for cookie '{}'", self.name));
+            }
+        }
+        if self.same_site == Some(SameSite::None) && !self.secure {
+            problems.push(format!(
+                "cookie '{}' has SameSite=None, which requires Secure",
+                self.name
+            ));
+        }
+        if !problems.is_empty() {
+            return Err(problems.join("; "));
+        }
+
+        let mut header = format!("{}={}", self.name, self.value);
+        if let Some(path) = &self.path {
+            header.push_str(&format!("; Path={}", path));
+        }
+        if let Some(domain) = &self.domain {
+            header.push_str(&format!("; Domain={}", domain));
+        }
+        if let Some(max_age) = self.max_age {
+            header.push_str(&format!("; Max-Age={}", max_age.as_secs()));
+        }
+        if let Some(expires) = self.expires {
+            header.push_str(&format!("; Expires={}", http_date(expires)));
+        }
+        match self.same_site {
+            Some(SameSite::Strict) => header.push_str("; SameSite=Strict"),
+            Some(SameSite::Lax) => header.push_str("; SameSite=Lax"),
+            Some(SameSite::None) => header.push_str("; SameSite=None"),
+            None => {}
+        }
+        if self.secure {
+            header.push_str("; Secure");
+        }
+        if self.http_only {
+            header.push_str("; HttpOnly");
+        }
+        Ok(header)
+    }
+}
+
+// Helper functions.
+// --------------------------------------------------
+
+// RFC 6265 cookie-name (an RFC 7230 token).
+fn is_token_char(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)
+}
+
+// RFC 6265 cookie-octet (values should be URL- or base64-encoded if needed).
+fn is_cookie_value_char(b: u8) -> bool {
+    matches!(b, 0x21 | 0x23..=0x2b | 0x2d..=0x3a | 0x3c..=0x5b | 0x5d..=0x7e)
+}
+
+// Tests.
+// --------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cookie_header_value() {
+        let cookie = Cookie::new("session", "abc123").with_max_age(Duration::from_secs(3600));
+        assert_eq!(
+            cookie.to_header_value().unwrap(),
+            "session=abc123; Path=/; Max-Age=3600; SameSite=Lax; Secure; HttpOnly"
+        );
+
+        let cookie = Cookie::removal("session")
+            .with_domain("fractic.io")
+            .without_same_site();
+        assert_eq!(
+            cookie.to_header_value().unwrap(),
+            "session=; Path=/; Domain=fractic.io; Max-Age=0; \
+             Expires=Thu, 01 Jan 1970 00:00:00 GMT; Secure; HttpOnly"
+        );
+    }
+
+    #[test]
+    fn test_invalid_cookie_rejected() {
+        let err = Cookie::new("bad name", "a;b")
+            .with_same_site(SameSite::None)
+            .without_secure()
+            .to_header_value()
+            .unwrap_err();
+        assert!(err.contains("invalid cookie name"));
+        assert!(err.contains("invalid value"));
+        assert!(err.contains("requires Secure"));
+    }
+}
@@ -0,0 +1,152 @@
+use std::future::Future;
+
+use aws_lambda_events::{
+    apigw::ApiGatewayProxyRequest,
+    http::{HeaderMap, HeaderName},
+};
+use lambda_runtime::LambdaEvent;
+use tracing::Span;
+
+// Request correlation IDs.
+// --------------------------------------------------
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+pub const CORRELATION_ID_HEADER: HeaderName = HeaderName::from_static("x-correlation-id");
+pub const TRACE_ID_HEADER: HeaderName = HeaderName::from_static("x-amzn-trace-id");
+
+// Identifiers which can be used to match a client-side report against the
+// server logs. All are optional, since they depend on how the lambda was
+// invoked (for example, the Lambda context is not available in unit tests).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CorrelationIds {
+    // API Gateway's request ID (request_context.request_id).
+    pub api_request_id: Option<String>,
+    // Lambda's invocation request ID (context.request_id).
+    pub lambda_request_id: Option<String>,
+    // AWS X-Ray trace header.
+    pub trace_id: Option<String>,
+    // Optional client-supplied X-Request-Id or X-Correlation-Id.
+    pub client_request_id: Option<String>,
+}
+
+impl CorrelationIds {
+    pub fn from_request(request: &ApiGatewayProxyRequest) -> Self {
+        CorrelationIds {
+            api_request_id: request.request_context.request_id.clone(),
+            lambda_request_id: None,
+            trace_id: header_value(&request.headers, &TRACE_ID_HEADER),
+            client_request_id: header_value(&request.headers, &REQUEST_ID_HEADER)
+                .or_else(|| header_value(&request.headers, &CORRELATION_ID_HEADER)),
+        }
+    }
+
+    pub fn from_event(event: &LambdaEvent<ApiGatewayProxyRequest>) -> Self {
+        let mut ids = Self::from_request(&event.payload);
+        if !event.context.request_id.is_empty() {
+            ids.lambda_request_id = Some(event.context.request_id.clone());
+        }
+        // Prefer the trace ID provided by the Lambda runtime, since it
+        // includes the segment created for this invocation.
+        if let Some(trace_id) = &event.context.xray_trace_id {
+            ids.trace_id = Some(trace_id.clone());
+        }
+        ids
+    }
+
+    // The ID echoed back to the client as X-Request-Id. API Gateway's ID is
+    // preferred since it is the one shown in the API Gateway access logs.
+    pub fn request_id(&self) -> Option<&str> {
+        self.api_request_id
+            .as_deref()
+            .or(self.lambda_request_id.as_deref())
+    }
+
+    pub(crate) fn span(&self) -> Span {
+        tracing::info_span!(
+            "request",
+            request_id = self.api_request_id.as_deref().unwrap_or("-"),
+            lambda_request_id = self.lambda_request_id.as_deref().unwrap_or("-"),
+            trace_id = self.trace_id.as_deref().unwrap_or("-"),
+            correlation_id = self.client_request_id.as_deref().unwrap_or("-"),
+        )
+    }
+}
+
+tokio::task_local! {
+    static CURRENT_CORRELATION_IDS: CorrelationIds;
+}
+
+// Returns the correlation IDs of the request currently being handled by
+// handle_route, if any.
+pub fn current_correlation_ids() -> Option<CorrelationIds> {
+    CURRENT_CORRELATION_IDS.try_with(|ids| ids.clone()).ok()
+}
+
+pub(crate) async fn with_correlation_ids<F: Future>(ids: CorrelationIds, f: F) -> F::Output {
+    CURRENT_CORRELATION_IDS.scope(ids, f).await
+}
+
+// Helper functions.
+// --------------------------------------------------
+
+fn header_value(headers: &HeaderMap, name: &HeaderName) -> Option<String> {
+    headers
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+// Tests.
+// --------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aws_lambda_events::apigw::ApiGatewayProxyRequestContext;
+    use lambda_runtime::Context;
+
+    fn create_request() -> ApiGatewayProxyRequest {
+        let mut headers = HeaderMap::new();
+        headers.insert(CORRELATION_ID_HEADER, "client-id".parse().unwrap());
+        headers.insert(TRACE_ID_HEADER, "Root=header-trace".parse().unwrap());
+        ApiGatewayProxyRequest {
+            headers,
+            request_context: ApiGatewayProxyRequestContext {
+                request_id: Some("apigw-id".into()),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_from_request() {
+        let ids = CorrelationIds::from_request(&create_request());
+        assert_eq!(ids.api_request_id.as_deref(), Some("apigw-id"));
+        assert_eq!(ids.lambda_request_id, None);
+        assert_eq!(ids.trace_id.as_deref(), Some("Root=header-trace"));
+        assert_eq!(ids.client_request_id.as_deref(), Some("client-id"));
+        assert_eq!(ids.request_id(), Some("apigw-id"));
+    }
+
+    #[test]
+    fn test_from_event_prefers_lambda_trace_id() {
+        let context = Context {
+            request_id: "lambda-id".into(),
+            xray_trace_id: Some("Root=lambda-trace".into()),
+            ..Default::default()
+        };
+        let ids = CorrelationIds::from_event(&LambdaEvent::new(create_request(), context));
+        assert_eq!(ids.lambda_request_id.as_deref(), Some("lambda-id"));
+        assert_eq!(ids.trace_id.as_deref(), Some("Root=lambda-trace"));
+    }
+
+    #[tokio::test]
+    async fn test_current_correlation_ids() {
+        assert!(current_correlation_ids().is_none());
+        let ids = CorrelationIds::from_request(&create_request());
+        let current = with_correlation_ids(ids.clone(), async { current_correlation_ids() }).await;
+        assert_eq!(current, Some(ids));
+    }
+}
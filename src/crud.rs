@@ -8,13 +8,23 @@ use fractic_server_error::CriticalError;
 use fractic_server_error::ServerError;
 use lambda_runtime::Error;
 use lambda_runtime::LambdaEvent;
+use std::time::SystemTime;
 
-use crate::{build_error, build_result, parse_request_data, InvalidRequestError};
+use crate::{
+    build_error, build_result, caching::parse_timestamp, parse_request_data, ApiResponse,
+    IntoApiResponse, InvalidRequestError,
+};
 
 pub struct CrudRouteScaffolding {
     dynamo_util: DynamoUtil<aws_sdk_dynamodb::Client>,
+    // Field of the stored item holding its last modification time (as an RFC
+    // 3339 string or epoch timestamp), sent as the Last-Modified header of
+    // reads to support conditional requests.
+    last_modified_field: Option<String>,
 }
 
+const DEFAULT_LAST_MODIFIED_FIELD: &str = "updated_at";
+
 #[derive(Debug)]
 enum RequestProperties<T: DynamoObject> {
     Read { id: PkSk },
@@ -33,7 +43,22 @@ impl CrudRouteScaffolding {
     pub async fn new<EnvConfig: EnvConfigEnum>(table_var: EnvConfig) -> Result<Self, ServerError> {
         let env = load_env::<EnvConfig>()?;
         let dynamo_util = DynamoUtil::new(env.clone_into()?, env.get(&table_var)?).await?;
-        Ok(CrudRouteScaffolding { dynamo_util })
+        Ok(CrudRouteScaffolding {
+            dynamo_util,
+            last_modified_field: Some(DEFAULT_LAST_MODIFIED_FIELD.to_string()),
+        })
+    }
+
+    // Overrides the item field used for the Last-Modified header ('updated_at'
+    // by default). Items without the field are sent without the header.
+    pub fn with_last_modified_field(mut self, field: &str) -> Self {
+        self.last_modified_field = Some(field.to_string());
+        self
+    }
+
+    pub fn without_last_modified(mut self) -> Self {
+        self.last_modified_field = None;
+        self
     }
 
     pub async fn handle_request<T: DynamoObject>(
@@ -48,7 +73,15 @@ impl CrudRouteScaffolding {
                 }
             }
             Ok(RequestProperties::<T>::Read { id }) => match self.read::<T>(id).await {
-                Ok(result) => build_result(result),
+                Ok(result) => {
+                    let last_modified = self.last_modified(&result);
+                    let response = ApiResponse::ok(result);
+                    match last_modified {
+                        Some(last_modified) => response.with_last_modified(last_modified),
+                        None => response,
+                    }
+                    .into_api_response()
+                }
                 Err(error) => build_error(error),
             },
             Ok(RequestProperties::<T>::Update { object }) => match self.update::<T>(object).await {
@@ -125,6 +158,14 @@ impl CrudRouteScaffolding {
         }
     }
 
+    fn last_modified<T: DynamoObject>(&self, object: &T) -> Option<SystemTime> {
+        let field = self.last_modified_field.as_deref()?;
+        serde_json::to_value(object)
+            .ok()?
+            .get(field)
+            .and_then(parse_timestamp)
+    }
+
     async fn update<T: DynamoObject>(&self, object: T) -> Result<(), ServerError> {
         self.dynamo_util.update_item(&object).await
     }
@@ -1,5 +1,18 @@
-use fractic_server_error::{define_client_error, define_sensitive_error};
+use fractic_server_error::{
+    define_client_error, define_internal_error, define_sensitive_error, define_user_error,
+};
 
 define_client_error!(InvalidRequestError, "Request is invalid: {details}.", { details: &str });
 define_client_error!(InvalidRouteError, "Route '{route:?}' does not exist.", { route: Option<String> });
+define_client_error!(UnsupportedMediaTypeError, "Unsupported media type: {details}.", { details: &str });
+define_client_error!(PayloadTooLargeError, "Request body is too large: {details}.", { details: &str });
 define_sensitive_error!(UnauthorizedError, "Not authorized to access this resource.");
+define_user_error!(ValidationError, "Request is invalid: {details}.", { details: &str });
+define_user_error!(
+    RequestTimeoutError,
+    "The server took too long to respond. Please try again."
+);
+define_internal_error!(InvalidRoutingConfigError, "Routing config is invalid: {details}.", { details: &str });
+define_internal_error!(InvalidOpenApiDocsError, "OpenAPI docs are invalid: {details}.", { details: &str });
+define_internal_error!(InvalidSamTemplateError, "SAM template config is invalid: {details}.", { details: &str });
+define_internal_error!(InvalidResponseError, "Response is invalid: {details}.", { details: &str });
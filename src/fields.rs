@@ -0,0 +1,309 @@
+use std::{collections::BTreeMap, future::Future};
+
+use aws_lambda_events::apigw::ApiGatewayProxyRequest;
+use fractic_server_error::ServerError;
+use serde_json::{Map, Value};
+
+use crate::errors::ValidationError;
+
+// Field selection (sparse fieldsets).
+// --------------------------------------------------
+//
+// Clients can request only part of the response data with the 'fields' query
+// parameter, a comma-separated list of dotted paths, for example:
+//
+//   GET /orders?id=ORDER%231&fields=id,status,items.name
+//
+// The data returned through build_result (including by CRUD reads and routes
+// registered with the macros) is projected down to the requested paths.
+// Arrays are traversed transparently, so paths refer to the fields of their
+// elements, both for list responses ('fields=id,name' on a Vec) and for lists
+// inside an envelope ('fields=items.id,next_cursor'). The ResponseWrapper
+// envelope itself (ok, error, request_id) is never affected.
+//
+// Selection is enabled for all fields by default, and can be restricted per
+// route (see FieldSelection). Routes with selection disabled ignore the
+// parameter, so that they can use a 'fields' query parameter of their own.
+
+pub const FIELDS_QUERY_PARAM: &str = "fields";
+
+// Upper bound on the number of requested paths, to bound projection cost.
+const MAX_SELECTED_FIELDS: usize = 100;
+
+#[derive(Debug, Clone, Default)]
+pub struct FieldSelection {
+    pub disabled: bool,
+    // If set, only these paths (and the fields nested under them) can be
+    // selected. Requesting any other field fails validation.
+    pub allowed: Option<Vec<String>>,
+}
+
+impl FieldSelection {
+    pub fn disabled() -> Self {
+        FieldSelection {
+            disabled: true,
+            allowed: None,
+        }
+    }
+
+    pub fn only(allowed: &[&str]) -> Self {
+        FieldSelection {
+            disabled: false,
+            allowed: Some(allowed.iter().map(|f| f.to_string()).collect()),
+        }
+    }
+}
+
+// Tree of selected paths. A node without children selects the entire value.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct FieldTree(BTreeMap<String, FieldTree>);
+
+impl FieldTree {
+    fn insert(&mut self, path: &[&str]) {
+        let Some((first, rest)) = path.split_first() else {
+            return;
+        };
+        match self.0.get_mut(*first) {
+            // Already selected in its entirety.
+            Some(node) if node.0.is_empty() => {}
+            Some(node) if rest.is_empty() => node.0.clear(),
+            Some(node) => node.insert(rest),
+            None => {
+                let mut node = FieldTree::default();
+                node.insert(rest);
+                self.0.insert(first.to_string(), node);
+            }
+        }
+    }
+
+    // Keeps only the selected fields of the value.
+    pub(crate) fn project(&self, value: Value) -> Value {
+        match value {
+            Value::Array(items) => items.into_iter().map(|v| self.project(v)).collect(),
+            Value::Object(mut object) => Value::Object(
+                self.0
+                    .iter()
+                    .filter_map(|(key, node)| {
+                        let value = object.remove(key)?;
+                        Some(match node.0.is_empty() {
+                            true => (key.clone(), value),
+                            false => (key.clone(), node.project(value)),
+                        })
+                    })
+                    .collect::<Map<_, _>>(),
+            ),
+            // Scalars cannot be projected further.
+            value => value,
+        }
+    }
+}
+
+// Parses the 'fields' query parameter of the request, if selection is
+// enabled for the route and the parameter is set.
+pub(crate) fn parse_field_selection(
+    request: &ApiGatewayProxyRequest,
+    config: &FieldSelection,
+) -> Result<Option<FieldTree>, ServerError> {
+    if config.disabled {
+        return Ok(None);
+    }
+    let values = request
+        .multi_value_query_string_parameters
+        .all(FIELDS_QUERY_PARAM)
+        .or_else(|| request.query_string_parameters.all(FIELDS_QUERY_PARAM));
+    let Some(values) = values else {
+        return Ok(None);
+    };
+    let paths: Vec<&str> = values
+        .iter()
+        .flat_map(|v| v.split(','))
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .collect();
+    if paths.is_empty() {
+        return Ok(None);
+    }
+    if paths.len() > MAX_SELECTED_FIELDS {
+        return Err(ValidationError::new(&format!(
+            "at most {} fields can be selected",
+            MAX_SELECTED_FIELDS
+        )));
+    }
+
+    let mut problems = Vec::new();
+    let mut tree = FieldTree::default();
+    for path in paths {
+        let segments: Vec<&str> = path.split('.').collect();
+        if segments.iter().any(|s| s.is_empty()) {
+            problems.push(format!("'{}' is not a valid field path", path));
+        } else if !is_allowed(path, config) {
+            problems.push(format!("field '{}' cannot be selected", path));
+        } else {
+            tree.insert(&segments);
+        }
+    }
+    if !problems.is_empty() {
+        return Err(ValidationError::new(&problems.join("; ")));
+    }
+    Ok(Some(tree))
+}
+
+tokio::task_local! {
+    static CURRENT_FIELD_SELECTION: Option<FieldTree>;
+}
+
+// Returns the field selection of the request currently being handled by
+// handle_route, if any.
+pub(crate) fn current_field_selection() -> Option<FieldTree> {
+    CURRENT_FIELD_SELECTION
+        .try_with(|selection| selection.clone())
+        .ok()
+        .flatten()
+}
+
+pub(crate) async fn with_field_selection<F: Future>(
+    selection: Option<FieldTree>,
+    f: F,
+) -> F::Output {
+    CURRENT_FIELD_SELECTION.scope(selection, f).await
+}
+
+// Helper functions.
+// --------------------------------------------------
+
+// A path is allowed if it is (or is nested under) one of the allowed paths.
+fn is_allowed(path: &str, config: &FieldSelection) -> bool {
+    match &config.allowed {
+        None => true,
+        Some(allowed) => allowed.iter().any(|allowed| {
+            path == allowed
+                || path
+                    .strip_prefix(allowed.as_str())
+                    .is_some_and(|rest| rest.starts_with('.'))
+        }),
+    }
+}
+
+// Tests.
+// --------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    fn create_request(fields: &str) -> ApiGatewayProxyRequest {
+        ApiGatewayProxyRequest {
+            query_string_parameters: HashMap::from([(
+                FIELDS_QUERY_PARAM.to_string(),
+                fields.to_string(),
+            )])
+            .into(),
+            ..Default::default()
+        }
+    }
+
+    fn select(fields: &str, value: Value) -> Value {
+        parse_field_selection(&create_request(fields), &FieldSelection::default())
+            .unwrap()
+            .unwrap()
+            .project(value)
+    }
+
+    #[test]
+    fn test_project_nested_and_arrays() {
+        let order = json!({
+            "id": "ORDER#1",
+            "status": "open",
+            "customer": { "name": "Jane", "email": "jane@fractic.io" },
+            "items": [
+                { "name": "book", "price": 10 },
+                { "name": "pen", "price": 2 },
+            ],
+        });
+        assert_eq!(
+            select("id, customer.name,items.name,missing", order.clone()),
+            json!({
+                "id": "ORDER#1",
+                "customer": { "name": "Jane" },
+                "items": [{ "name": "book" }, { "name": "pen" }],
+            })
+        );
+        // Selecting a parent selects all of its fields.
+        assert_eq!(
+            select("customer.name,customer", order.clone()),
+            json!({ "customer": { "name": "Jane", "email": "jane@fractic.io" } })
+        );
+        // Paths refer to the elements of list responses.
+        assert_eq!(
+            select("id", json!([order.clone(), { "id": "ORDER#2" }])),
+            json!([{ "id": "ORDER#1" }, { "id": "ORDER#2" }])
+        );
+    }
+
+    #[test]
+    fn test_selection_config() {
+        let request = create_request("id,customer.email");
+        let config = FieldSelection::only(&["id", "customer"]);
+        assert!(parse_field_selection(&request, &config).unwrap().is_some());
+
+        let config = FieldSelection::only(&["id", "customer.name"]);
+        let err = parse_field_selection(&request, &config).unwrap_err();
+        assert!(err
+            .message()
+            .contains("'customer.email' cannot be selected"));
+
+        let config = FieldSelection::disabled();
+        assert!(parse_field_selection(&request, &config).unwrap().is_none());
+
+        let request = create_request("id,,items..name");
+        let err = parse_field_selection(&request, &FieldSelection::default()).unwrap_err();
+        assert!(err
+            .message()
+            .contains("'items..name' is not a valid field path"));
+    }
+
+    #[tokio::test]
+    async fn test_routed_field_selection() {
+        use crate::{
+            request::RequestMetadata,
+            routing::{box_route_handler, AccessLevel, FunctionRoute, RoutingConfig},
+            testing::{TestClient, TestRequest},
+        };
+        use aws_lambda_events::apigw::ApiGatewayProxyResponse;
+        use lambda_runtime::{Error, LambdaEvent};
+
+        async fn profile(
+            _: LambdaEvent<ApiGatewayProxyRequest>,
+            _: RequestMetadata,
+        ) -> Result<ApiGatewayProxyResponse, Error> {
+            crate::build_result(json!({ "name": "Jane", "email": "jane@fractic.io" }))
+        }
+
+        let route = |field_selection| {
+            FunctionRoute::new(AccessLevel::Guest, box_route_handler(profile))
+                .with_field_selection(field_selection)
+        };
+        let client = TestClient::new(
+            RoutingConfig::builder()
+                .function("profile", route(FieldSelection::only(&["name"])))
+                .function("raw-profile", route(FieldSelection::disabled()))
+                .build()
+                .unwrap(),
+        );
+
+        let response = client
+            .send(TestRequest::post("profile").query(FIELDS_QUERY_PARAM, "name"))
+            .await;
+        assert_eq!(response.data::<Value>(), json!({ "name": "Jane" }));
+        client
+            .send(TestRequest::post("profile").query(FIELDS_QUERY_PARAM, "email"))
+            .await
+            .assert_error_contains("'email' cannot be selected");
+        let response = client
+            .send(TestRequest::post("raw-profile").query(FIELDS_QUERY_PARAM, "name"))
+            .await;
+        assert_eq!(response.data::<Value>()["email"], "jane@fractic.io");
+    }
+}
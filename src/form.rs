@@ -0,0 +1,325 @@
+use std::collections::BTreeMap;
+
+use aws_lambda_events::{apigw::ApiGatewayProxyRequest, http::header::CONTENT_TYPE};
+use fractic_server_error::ServerError;
+use serde::de::DeserializeOwned;
+
+use crate::{body::request_body_bytes, errors::InvalidRequestError, params::deserialize_params};
+
+// Form request bodies.
+// --------------------------------------------------
+//
+// Form bodies can be used with the existing registration macros:
+//
+//   - application/x-www-form-urlencoded bodies are parsed into any typed
+//     struct by parse_request_data, with the same conversion rules as
+//     parse_query.
+//   - multipart/form-data bodies are parsed into a MultipartForm (containing
+//     the text fields and file parts) by parse_multipart_form, which the
+//     macros use for routes taking a MultipartForm. parse_request_data
+//     rejects multipart bodies.
+//
+// Per-part size limits for multipart bodies are enforced by handle_route,
+// based on RequestBodyConfig::max_part_size.
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MultipartForm {
+    // Text fields (parts without a filename), by name. Repeated names keep
+    // all values, in order.
+    pub fields: BTreeMap<String, Vec<String>>,
+    // File parts, in order.
+    pub files: Vec<FilePart>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilePart {
+    pub name: String,
+    pub filename: Option<String>,
+    pub content_type: Option<String>,
+    pub data: Vec<u8>,
+}
+
+impl MultipartForm {
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields
+            .get(name)
+            .and_then(|values| values.first())
+            .map(String::as_str)
+    }
+
+    pub fn file(&self, name: &str) -> Option<&FilePart> {
+        self.files.iter().find(|f| f.name == name)
+    }
+
+    // Parses the text fields into a typed struct, with the same conversion
+    // rules as parse_query.
+    pub fn parse_fields<T: DeserializeOwned>(&self) -> Result<T, ServerError> {
+        deserialize_params(self.fields.clone(), "form fields")
+    }
+}
+
+// Raw part of a multipart body.
+pub(crate) struct Part<'a> {
+    pub(crate) name: String,
+    pub(crate) filename: Option<String>,
+    pub(crate) content_type: Option<String>,
+    pub(crate) data: &'a [u8],
+}
+
+// Form request body utils.
+// --------------------------------------------------
+
+pub fn parse_form_urlencoded<T>(request: &ApiGatewayProxyRequest) -> Result<T, ServerError>
+where
+    T: DeserializeOwned,
+{
+    let body = request_body_bytes(request)?;
+    let mut params: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (key, value) in form_urlencoded::parse(&body) {
+        params
+            .entry(key.into_owned())
+            .or_default()
+            .push(value.into_owned());
+    }
+    deserialize_params(params, "form fields")
+}
+
+pub fn parse_multipart_form(
+    request: &ApiGatewayProxyRequest,
+) -> Result<MultipartForm, ServerError> {
+    let boundary = multipart_boundary(request)?;
+    let body = request_body_bytes(request)?;
+    let mut form = MultipartForm::default();
+    for part in parse_multipart(&body, &boundary)? {
+        match part.filename {
+            None => {
+                let value = String::from_utf8(part.data.to_vec()).map_err(|e| {
+                    InvalidRequestError::with_debug(
+                        &format!("form field '{}' is not valid UTF-8", part.name),
+                        &e,
+                    )
+                })?;
+                form.fields.entry(part.name).or_default().push(value);
+            }
+            Some(filename) => form.files.push(FilePart {
+                name: part.name,
+                filename: Some(filename),
+                content_type: part.content_type,
+                data: part.data.to_vec(),
+            }),
+        }
+    }
+    Ok(form)
+}
+
+pub(crate) fn multipart_boundary(request: &ApiGatewayProxyRequest) -> Result<String, ServerError> {
+    request
+        .headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|content_type| {
+            content_type.split(';').skip(1).find_map(|param| {
+                let (key, value) = param.split_once('=')?;
+                (key.trim().eq_ignore_ascii_case("boundary"))
+                    .then(|| value.trim().trim_matches('"').to_string())
+            })
+        })
+        .filter(|boundary| !boundary.is_empty())
+        .ok_or_else(|| InvalidRequestError::new("multipart body is missing a boundary"))
+}
+
+pub(crate) fn parse_multipart<'a>(
+    body: &'a [u8],
+    boundary: &str,
+) -> Result<Vec<Part<'a>>, ServerError> {
+    let delimiter = format!("--{}", boundary).into_bytes();
+    let malformed = |details: &str| {
+        InvalidRequestError::new(&format!("malformed multipart body ({})", details))
+    };
+
+    let mut pos = find(body, &delimiter, 0).ok_or_else(|| malformed("missing boundary"))?;
+    let mut parts = Vec::new();
+    loop {
+        pos += delimiter.len();
+        if body[pos..].starts_with(b"--") {
+            return Ok(parts);
+        }
+        pos = skip_line_break(body, pos).ok_or_else(|| malformed("missing line break"))?;
+
+        // Line breaks may be CRLF (as required) or bare LF (as sent by some
+        // clients), both in the part headers and before the next delimiter.
+        let (headers_end, data_start) =
+            find_blank_line(body, pos).ok_or_else(|| malformed("missing part headers"))?;
+        let headers = std::str::from_utf8(&body[pos..headers_end])
+            .map_err(|_| malformed("part headers are not valid UTF-8"))?;
+        let next_delimiter = find(body, &[b"\n", delimiter.as_slice()].concat(), data_start)
+            .ok_or_else(|| malformed("missing closing boundary"))?;
+        let data_end = match next_delimiter > data_start && body[next_delimiter - 1] == b'\r' {
+            true => next_delimiter - 1,
+            false => next_delimiter,
+        };
+
+        let mut name = None;
+        let mut filename = None;
+        let mut content_type = None;
+        for line in headers.lines() {
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            if key.trim().eq_ignore_ascii_case("content-disposition") {
+                for param in value.split(';').skip(1) {
+                    if let Some((k, v)) = param.split_once('=') {
+                        let v = v.trim().trim_matches('"').to_string();
+                        match k.trim().to_ascii_lowercase().as_str() {
+                            "name" => name = Some(v),
+                            "filename" => filename = Some(v),
+                            _ => {}
+                        }
+                    }
+                }
+            } else if key.trim().eq_ignore_ascii_case("content-type") {
+                content_type = Some(value.trim().to_string());
+            }
+        }
+
+        parts.push(Part {
+            name: name.ok_or_else(|| malformed("part is missing a name"))?,
+            filename,
+            content_type,
+            data: &body[data_start..data_end],
+        });
+        // Continue from the next delimiter.
+        pos = next_delimiter + 1;
+    }
+}
+
+// Helper functions.
+// --------------------------------------------------
+
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    haystack
+        .get(from..)?
+        .windows(needle.len())
+        .position(|window| window == needle)
+        .map(|i| i + from)
+}
+
+fn skip_line_break(body: &[u8], pos: usize) -> Option<usize> {
+    if body[pos..].starts_with(b"\r\n") {
+        Some(pos + 2)
+    } else if body[pos..].starts_with(b"\n") {
+        Some(pos + 1)
+    } else {
+        None
+    }
+}
+
+// Returns the end of the header lines starting at pos, and the start of the
+// content after the blank line following them.
+fn find_blank_line(body: &[u8], mut pos: usize) -> Option<(usize, usize)> {
+    loop {
+        let line_end = find(body, b"\n", pos)?;
+        if matches!(&body[pos..line_end], b"" | b"\r") {
+            return Some((pos, line_end + 1));
+        }
+        pos = line_end + 1;
+    }
+}
+
+// Tests.
+// --------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    const MULTIPART_BODY: &str = "--XyZ\r\n\
+        Content-Disposition: form-data; name=\"title\"\r\n\
+        \r\n\
+        Hello\r\n\
+        --XyZ\r\n\
+        Content-Disposition: form-data; name=\"upload\"; filename=\"a.txt\"\r\n\
+        Content-Type: text/plain\r\n\
+        \r\n\
+        file\r\ncontents\r\n\
+        --XyZ--\r\n";
+
+    fn create_request(content_type: &str, body: &str) -> ApiGatewayProxyRequest {
+        let mut request = ApiGatewayProxyRequest {
+            body: Some(body.to_string()),
+            ..Default::default()
+        };
+        request
+            .headers
+            .insert(CONTENT_TYPE, content_type.parse().unwrap());
+        request
+    }
+
+    #[test]
+    fn test_parse_form_urlencoded() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct TestForm {
+            name: String,
+            age: u32,
+            subscribe: bool,
+        }
+        let request = create_request(
+            "application/x-www-form-urlencoded",
+            "name=Jane+Doe&age=30&subscribe=true",
+        );
+        assert_eq!(
+            parse_form_urlencoded::<TestForm>(&request).unwrap(),
+            TestForm {
+                name: "Jane Doe".to_string(),
+                age: 30,
+                subscribe: true,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_multipart_form() {
+        let request = create_request("multipart/form-data; boundary=\"XyZ\"", MULTIPART_BODY);
+        let form = parse_multipart_form(&request).unwrap();
+        assert_eq!(form.field("title"), Some("Hello"));
+        let file = form.file("upload").unwrap();
+        assert_eq!(file.filename.as_deref(), Some("a.txt"));
+        assert_eq!(file.content_type.as_deref(), Some("text/plain"));
+        assert_eq!(file.data, b"file\r\ncontents");
+    }
+
+    #[test]
+    fn test_parse_multipart_missing_boundary() {
+        let request = create_request("multipart/form-data", MULTIPART_BODY);
+        assert!(parse_multipart_form(&request).is_err());
+    }
+
+    #[test]
+    fn test_parse_multipart_bare_line_feeds() {
+        let body = MULTIPART_BODY.replace("\r\n", "\n");
+        let request = create_request("multipart/form-data; boundary=XyZ", &body);
+        let form = parse_multipart_form(&request).unwrap();
+        assert_eq!(form.field("title"), Some("Hello"));
+        let file = form.file("upload").unwrap();
+        assert_eq!(file.content_type.as_deref(), Some("text/plain"));
+        assert_eq!(file.data, b"file\ncontents");
+    }
+
+    #[test]
+    fn test_parse_request_body_multipart() {
+        let request = create_request("multipart/form-data; boundary=XyZ", MULTIPART_BODY);
+        let form = crate::parse_request_body!(MultipartForm, &request).unwrap();
+        assert_eq!(form.file("upload").unwrap().data, b"file\r\ncontents");
+
+        #[derive(Deserialize, Debug)]
+        #[allow(dead_code)]
+        struct Other {
+            title: String,
+        }
+        let err = crate::parse_request_body!(Other, &request).unwrap_err();
+        assert!(err
+            .message()
+            .contains("only accepted by routes taking a MultipartForm"));
+    }
+}
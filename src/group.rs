@@ -0,0 +1,336 @@
+use std::sync::Arc;
+
+use aws_lambda_events::apigw::ApiGatewayProxyRequest;
+use lambda_runtime::LambdaEvent;
+
+use crate::{
+    builder::{normalize_path, RoutingConfigBuilder},
+    request::RequestMetadata,
+    response::build_error,
+    routing::{AccessLevel, CrudRoute, FunctionRoute, RouteFuture, RouteHandler},
+    validators::{AsyncValidator, ValidationContext},
+};
+
+// Route groups.
+// --------------------------------------------------
+//
+// Groups apply a path prefix, a default access level and shared middleware to
+// all contained routes (including those of nested groups), for example:
+//
+//   RoutingConfig::builder()
+//       .group(
+//           RouteGroup::new("admin")
+//               .access_level(AccessLevel::Admin)
+//               .validator(require_admin)
+//               .function("users/list", list_users_route)
+//               .group(RouteGroup::new("reports").function("daily", daily_route)),
+//       )
+//       .build()?;
+//
+// registers "admin/users/list" and "admin/reports/daily". Routes declared with
+// AccessLevel::Inherit take the access level of the innermost group which sets
+// one, while routes with any other access level keep it (per-route override).
+//
+// Middleware runs outermost group first, in the order it was added.
+
+pub type Middleware = Arc<
+    dyn Fn(LambdaEvent<ApiGatewayProxyRequest>, RequestMetadata, Next) -> RouteFuture + Send + Sync,
+>;
+
+// Function and CRUD routes of a group, with the group's settings applied.
+type FlattenedRoutes = (Vec<(String, FunctionRoute)>, Vec<(String, CrudRoute)>);
+
+// Remainder of the handler chain, passed to middleware.
+pub struct Next(Arc<RouteHandler>);
+
+impl Next {
+    pub fn run(
+        self,
+        event: LambdaEvent<ApiGatewayProxyRequest>,
+        metadata: RequestMetadata,
+    ) -> RouteFuture {
+        (self.0)(event, metadata)
+    }
+}
+
+#[derive(Default)]
+pub struct RouteGroup {
+    prefix: String,
+    access_level: Option<AccessLevel>,
+    middleware: Vec<Middleware>,
+    function_routes: Vec<(String, FunctionRoute)>,
+    crud_routes: Vec<(String, CrudRoute)>,
+    groups: Vec<RouteGroup>,
+}
+
+impl RouteGroup {
+    pub fn new(prefix: &str) -> Self {
+        RouteGroup {
+            prefix: prefix.to_string(),
+            ..Default::default()
+        }
+    }
+
+    // Access level of contained routes declared with AccessLevel::Inherit.
+    pub fn access_level(mut self, access_level: AccessLevel) -> Self {
+        self.access_level = Some(access_level);
+        self
+    }
+
+    pub fn middleware(
+        mut self,
+        middleware: impl Fn(LambdaEvent<ApiGatewayProxyRequest>, RequestMetadata, Next) -> RouteFuture
+            + Send
+            + Sync
+            + 'static,
+    ) -> Self {
+        self.middleware.push(Arc::new(middleware));
+        self
+    }
+
+    // Shorthand for a middleware which runs the validator before the route,
+    // responding with its error if it fails. Since the request data type
+    // differs between routes, group validators only receive the request and
+    // metadata.
+    pub fn validator(self, validator: impl AsyncValidator<()> + 'static) -> Self {
+        let validator = Arc::new(validator);
+        self.middleware(move |event, metadata, next| {
+            let validator = validator.clone();
+            Box::pin(async move {
+                let validation = {
+                    let ctx = ValidationContext {
+                        request: &event.payload,
+                        metadata: &metadata,
+                        data: &(),
+                        state: &(),
+                    };
+                    validator.validate(&ctx).await
+                };
+                match validation {
+                    Ok(_) => next.run(event, metadata).await,
+                    Err(validation_error) => build_error(validation_error),
+                }
+            })
+        })
+    }
+
+    pub fn function(mut self, path: &str, route: FunctionRoute) -> Self {
+        self.function_routes.push((path.to_string(), route));
+        self
+    }
+
+    pub fn crud(mut self, path: &str, route: CrudRoute) -> Self {
+        self.crud_routes.push((path.to_string(), route));
+        self
+    }
+
+    pub fn group(mut self, group: RouteGroup) -> Self {
+        self.groups.push(group);
+        self
+    }
+
+    // Resolves the group's settings into its routes and those of its nested
+    // groups.
+    pub(crate) fn flatten(self) -> FlattenedRoutes {
+        let mut function_routes = self.function_routes;
+        let mut crud_routes = self.crud_routes;
+        for group in self.groups {
+            let (nested_function_routes, nested_crud_routes) = group.flatten();
+            function_routes.extend(nested_function_routes);
+            crud_routes.extend(nested_crud_routes);
+        }
+
+        let prefix = |path: String| normalize_path(&format!("{}/{}", self.prefix, path));
+        let inherit = |access_level: &mut AccessLevel| {
+            if let (AccessLevel::Inherit, Some(group_access_level)) =
+                (*access_level, self.access_level)
+            {
+                *access_level = group_access_level;
+            }
+        };
+        let function_routes = function_routes
+            .into_iter()
+            .map(|(path, mut route)| {
+                inherit(&mut route.access_level);
+                route.handler = wrap_handler(route.handler, &self.middleware);
+                (prefix(path), route)
+            })
+            .collect();
+        let crud_routes = crud_routes
+            .into_iter()
+            .map(|(path, mut route)| {
+                inherit(&mut route.create_access_level);
+                inherit(&mut route.read_access_level);
+                inherit(&mut route.update_access_level);
+                inherit(&mut route.delete_access_level);
+                route.handler = wrap_handler(route.handler, &self.middleware);
+                (prefix(path), route)
+            })
+            .collect();
+        (function_routes, crud_routes)
+    }
+}
+
+impl RoutingConfigBuilder {
+    pub fn group(self, group: RouteGroup) -> Self {
+        let (function_routes, crud_routes) = group.flatten();
+        let builder = function_routes
+            .into_iter()
+            .fold(self, |builder, (path, route)| {
+                builder.function(&path, route)
+            });
+        crud_routes
+            .into_iter()
+            .fold(builder, |builder, (path, route)| builder.crud(&path, route))
+    }
+}
+
+// Helper functions.
+// --------------------------------------------------
+
+fn wrap_handler(handler: RouteHandler, middleware: &[Middleware]) -> RouteHandler {
+    // Wrap in reverse, so that the first middleware ends up outermost.
+    middleware.iter().rev().fold(handler, |inner, middleware| {
+        let inner = Arc::new(inner);
+        let middleware = middleware.clone();
+        Box::new(move |event, metadata| middleware(event, metadata, Next(inner.clone())))
+    })
+}
+
+// Tests.
+// --------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        correlation::CorrelationIds,
+        errors::InvalidRequestError,
+        routing::{box_route_handler, RoutingConfig},
+        validators::ValidatorFuture,
+    };
+    use aws_lambda_events::apigw::ApiGatewayProxyResponse;
+    use lambda_runtime::Error;
+    use std::cell::RefCell;
+
+    thread_local! {
+        static CALLS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    }
+
+    fn record_call(name: &str) {
+        CALLS.with(|calls| calls.borrow_mut().push(name.to_string()));
+    }
+
+    async fn handler(
+        _: LambdaEvent<ApiGatewayProxyRequest>,
+        _: RequestMetadata,
+    ) -> Result<ApiGatewayProxyResponse, Error> {
+        record_call("handler");
+        crate::build_result("ok")
+    }
+
+    fn function_route(access_level: AccessLevel) -> FunctionRoute {
+        FunctionRoute::new(access_level, box_route_handler(handler))
+    }
+
+    fn recording_middleware(
+        name: &'static str,
+    ) -> impl Fn(LambdaEvent<ApiGatewayProxyRequest>, RequestMetadata, Next) -> RouteFuture {
+        move |event, metadata, next| {
+            record_call(name);
+            next.run(event, metadata)
+        }
+    }
+
+    fn reject_guests<'a>(ctx: &'a ValidationContext<'a, ()>) -> ValidatorFuture<'a> {
+        Box::pin(async move {
+            match ctx.metadata.is_authenticated {
+                true => Ok(()),
+                false => Err(InvalidRequestError::new("guests are not allowed")),
+            }
+        })
+    }
+
+    fn create_metadata(is_authenticated: bool) -> RequestMetadata {
+        RequestMetadata {
+            is_authenticated,
+            is_admin: false,
+            user_sub: None,
+            correlation_ids: CorrelationIds::from_request(&ApiGatewayProxyRequest::default()),
+            deadline: None,
+        }
+    }
+
+    fn create_config() -> RoutingConfig {
+        RoutingConfig::builder()
+            .group(
+                RouteGroup::new("/admin/")
+                    .access_level(AccessLevel::Admin)
+                    .middleware(recording_middleware("outer"))
+                    .function("users/list", function_route(AccessLevel::Inherit))
+                    .function("health", function_route(AccessLevel::Guest))
+                    .group(
+                        RouteGroup::new("reports")
+                            .middleware(recording_middleware("inner"))
+                            .validator(reject_guests)
+                            .function("daily", function_route(AccessLevel::Inherit)),
+                    ),
+            )
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_group_prefix_and_access_level() {
+        let config = create_config();
+        let routes = config.function_routes();
+        assert_eq!(routes["admin/users/list"].access_level, AccessLevel::Admin);
+        assert_eq!(
+            routes["admin/reports/daily"].access_level,
+            AccessLevel::Admin
+        );
+        // Explicit access levels override the group's.
+        assert_eq!(routes["admin/health"].access_level, AccessLevel::Guest);
+    }
+
+    #[tokio::test]
+    async fn test_group_middleware_order() {
+        let config = create_config();
+        let handler = &config.function_routes()["admin/reports/daily"].handler;
+        CALLS.with(|calls| calls.borrow_mut().clear());
+        handler(
+            LambdaEvent::new(Default::default(), Default::default()),
+            create_metadata(true),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            CALLS.with(|calls| calls.borrow().clone()),
+            vec!["outer", "inner", "handler"]
+        );
+
+        // A failing group validator stops the chain.
+        CALLS.with(|calls| calls.borrow_mut().clear());
+        let response = handler(
+            LambdaEvent::new(Default::default(), Default::default()),
+            create_metadata(false),
+        )
+        .await
+        .unwrap();
+        assert!(format!("{:?}", response.body).contains("\\\"ok\\\":false"));
+        assert_eq!(
+            CALLS.with(|calls| calls.borrow().clone()),
+            vec!["outer", "inner"]
+        );
+    }
+
+    #[test]
+    fn test_unresolved_inherit_rejected() {
+        let err = RoutingConfig::builder()
+            .group(RouteGroup::new("misc").function("ping", function_route(AccessLevel::Inherit)))
+            .build()
+            .err()
+            .unwrap();
+        assert!(format!("{:?}", err).contains("'misc/ping' inherits its access level"));
+    }
+}
@@ -0,0 +1,120 @@
+use aws_lambda_events::{
+    encodings::Body,
+    http::{Request, Response},
+};
+
+use crate::client::{ApiError, HttpTransport, TransportFuture};
+
+// HTTP transport.
+// --------------------------------------------------
+//
+// HttpTransport sending requests over the network with reqwest (requires the
+// 'http-client' feature), for example:
+//
+//   let client = ApiClient::new("https://api.example.com/prod", ReqwestTransport::new())
+//       .with_signer(TokenAuth::cognito(&token));
+//
+// TLS is handled by rustls, with the Mozilla root certificates. A custom
+// reqwest::Client (for example with timeouts or a proxy) can be provided with
+// ReqwestTransport::with_client.
+
+#[derive(Clone, Default)]
+pub struct ReqwestTransport {
+    client: reqwest::Client,
+}
+
+impl ReqwestTransport {
+    pub fn new() -> Self {
+        ReqwestTransport::default()
+    }
+
+    pub fn with_client(client: reqwest::Client) -> Self {
+        ReqwestTransport { client }
+    }
+}
+
+impl HttpTransport for ReqwestTransport {
+    fn send(&self, request: Request<Body>) -> TransportFuture<'_> {
+        Box::pin(async move {
+            let (parts, body) = request.into_parts();
+            let response = self
+                .client
+                .request(parts.method, parts.uri.to_string())
+                .headers(parts.headers)
+                .body(body.to_vec())
+                .send()
+                .await
+                .map_err(|e| ApiError::Transport(e.to_string()))?;
+
+            let status = response.status();
+            let headers = response.headers().clone();
+            let bytes = response
+                .bytes()
+                .await
+                .map_err(|e| ApiError::Transport(e.to_string()))?;
+            let mut http_response = Response::new(match bytes.is_empty() {
+                true => Body::Empty,
+                false => Body::Binary(bytes.to_vec()),
+            });
+            *http_response.status_mut() = status;
+            *http_response.headers_mut() = headers;
+            Ok(http_response)
+        })
+    }
+}
+
+// Tests.
+// --------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{ApiClient, FunctionEndpoint};
+    use serde::Serialize;
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+    };
+
+    #[derive(Serialize)]
+    struct Greeting {
+        name: String,
+    }
+
+    #[tokio::test]
+    async fn test_send_over_http() {
+        // Minimal HTTP server answering a single request.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut buffer = [0; 1024];
+            while !String::from_utf8_lossy(&request).contains("{\"name\":\"Jane\"}") {
+                let n = stream.read(&mut buffer).unwrap();
+                request.extend_from_slice(&buffer[..n]);
+            }
+            let body = "{\"ok\":true,\"data\":\"Hello, Jane\"}";
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\n\
+                 content-length: {}\r\nconnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            )
+            .unwrap();
+            String::from_utf8(request).unwrap()
+        });
+
+        const GREET: FunctionEndpoint<Greeting, String> = FunctionEndpoint::new("greet");
+        let client = ApiClient::new(&format!("http://{}/prod", addr), ReqwestTransport::new());
+        let greeting = Greeting {
+            name: "Jane".to_string(),
+        };
+        assert_eq!(client.call(&GREET, &greeting).await.unwrap(), "Hello, Jane");
+
+        let request = server.join().unwrap();
+        assert!(request.starts_with("POST /prod/greet HTTP/1.1"));
+        assert!(request.contains("content-type: application/json"));
+    }
+}
@@ -5,18 +5,89 @@
 // For this entire library, remap the serde_json crate to use it instead:
 extern crate serde_json_path_to_error as serde_json;
 
+// Allows the code generated by the route attribute (which refers to
+// ::fractic_aws_apigateway) to also be used within this crate.
+extern crate self as fractic_aws_apigateway;
+
 mod auth;
+mod body;
+mod builder;
+mod caching;
+mod client;
+mod compression;
 mod constants;
+mod cookie;
+mod correlation;
 mod crud;
 mod errors;
+mod fields;
+mod form;
+mod group;
+#[cfg(feature = "http-client")]
+mod http_client;
+#[cfg(feature = "local-server")]
+mod local_server;
 mod macros;
+mod metrics;
+#[cfg(feature = "openapi")]
+mod openapi;
+mod params;
+mod registry;
 mod request;
 mod response;
 mod routing;
+mod sam;
+mod schema;
+mod service;
+#[cfg(any(test, feature = "testing"))]
+mod testing;
+#[cfg(feature = "openapi")]
+mod typescript;
+mod validation;
+mod validators;
 
 pub use auth::*;
+pub use body::*;
+pub use builder::*;
+pub use client::*;
+pub use compression::*;
+pub use cookie::*;
+pub use correlation::*;
 pub use crud::*;
 pub use errors::*;
+pub use fields::*;
+pub use form::*;
+pub use group::*;
+#[cfg(feature = "http-client")]
+pub use http_client::*;
+#[cfg(feature = "local-server")]
+pub use local_server::*;
+pub use metrics::*;
+#[cfg(feature = "openapi")]
+pub use openapi::*;
+pub use params::*;
+pub use registry::*;
 pub use request::*;
 pub use response::*;
 pub use routing::*;
+pub use sam::*;
+pub use schema::*;
+pub use service::*;
+#[cfg(any(test, feature = "testing"))]
+pub use testing::*;
+pub use validation::*;
+pub use validators::*;
+
+pub use fractic_aws_apigateway_macros::route;
+
+// Schema derive used to document request and response types.
+#[cfg(feature = "openapi")]
+pub use schemars;
+
+// Dependencies used by the code generated by the route attribute.
+#[doc(hidden)]
+pub mod __private {
+    pub use aws_lambda_events;
+    pub use inventory;
+    pub use lambda_runtime;
+}
@@ -0,0 +1,315 @@
+use std::{
+    convert::Infallible,
+    net::SocketAddr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use aws_lambda_events::{
+    apigw::{ApiGatewayProxyRequestContext, ApiGatewayRequestAuthorizer},
+    encodings::Body,
+    http::{header::AUTHORIZATION, Request, Response, StatusCode},
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL, Engine};
+use hmac::{Hmac, Mac};
+use http_body_util::{BodyExt, Full};
+use hyper::{
+    body::{Bytes, Incoming},
+    server::conn::http1,
+    service::service_fn,
+};
+use hyper_util::rt::TokioIo;
+use serde_json::Value;
+use sha2::Sha256;
+use tokio::net::TcpListener;
+use tower_service::Service;
+
+use crate::{routing::RoutingConfig, service::Router};
+
+// Local development server.
+// --------------------------------------------------
+//
+// Serves a RoutingConfig over HTTP on the local machine (requires the
+// 'local-server' feature), for example:
+//
+//   LocalServer::new(config)
+//       .with_addr(([127, 0, 0, 1], 3000).into())
+//       .with_claims(fake_cognito_claims("user-1", "jane", &["admin"]))
+//       .serve()
+//       .await?;
+//
+// Requests are converted to API Gateway proxy events the same way as in
+// Router (the path after the base path becomes the {proxy+} parameter). The
+// authorizer claims are:
+//
+//   - if a JWT secret is set, the payload of the token in the Authorization
+//     header. The token must be signed with HS256 using the secret and not be
+//     expired, otherwise the request is rejected with 401 (as API Gateway
+//     would). Requests without a token are treated as guest requests.
+//   - otherwise, the configured fake claims, if set.
+//   - otherwise none, in which case the request is treated as a guest
+//     request.
+
+pub struct LocalServer {
+    router: Router,
+    addr: SocketAddr,
+    claims: Option<Value>,
+    jwt_secret: Option<String>,
+}
+
+impl LocalServer {
+    pub fn new(config: RoutingConfig) -> Self {
+        LocalServer {
+            router: Router::new(config),
+            addr: ([127, 0, 0, 1], 3000).into(),
+            claims: None,
+            jwt_secret: None,
+        }
+    }
+
+    pub fn with_addr(mut self, addr: SocketAddr) -> Self {
+        self.addr = addr;
+        self
+    }
+
+    pub fn with_base_path(mut self, base_path: &str) -> Self {
+        self.router = self.router.with_base_path(base_path);
+        self
+    }
+
+    pub fn with_claims(mut self, claims: Value) -> Self {
+        self.claims = Some(claims);
+        self
+    }
+
+    pub fn with_jwt_secret(mut self, secret: &str) -> Self {
+        self.jwt_secret = Some(secret.to_string());
+        self
+    }
+
+    pub async fn serve(self) -> std::io::Result<()> {
+        let listener = TcpListener::bind(self.addr).await?;
+        tracing::info!("Local server listening on http://{}.", self.addr);
+        let server = std::sync::Arc::new(self);
+        loop {
+            let (stream, _) = listener.accept().await?;
+            let server = server.clone();
+            tokio::spawn(async move {
+                let service = service_fn(move |request| {
+                    let server = server.clone();
+                    async move { Ok::<_, Infallible>(server.handle(request).await) }
+                });
+                if let Err(e) = http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await
+                {
+                    tracing::warn!("Local server connection error: {}", e);
+                }
+            });
+        }
+    }
+
+    async fn handle(&self, request: Request<Incoming>) -> Response<Full<Bytes>> {
+        let (parts, body) = request.into_parts();
+        let body = match body.collect().await {
+            Ok(collected) => collected.to_bytes(),
+            Err(e) => return plain_response(StatusCode::BAD_REQUEST, &e.to_string()),
+        };
+        let mut request = Request::from_parts(
+            parts,
+            match body.is_empty() {
+                true => Body::Empty,
+                false => Body::Binary(body.to_vec()),
+            },
+        );
+
+        let claims = match self.request_claims(&request) {
+            Ok(claims) => claims,
+            Err(reason) => return plain_response(StatusCode::UNAUTHORIZED, &reason),
+        };
+        let mut request_context = ApiGatewayProxyRequestContext {
+            http_method: request.method().clone(),
+            authorizer: ApiGatewayRequestAuthorizer::default(),
+            ..Default::default()
+        };
+        if let Some(claims) = claims {
+            request_context
+                .authorizer
+                .fields
+                .insert("claims".to_string(), claims);
+        }
+        request.extensions_mut().insert(request_context);
+
+        let method = request.method().clone();
+        let path = request.uri().path().to_string();
+        let mut router = self.router.clone();
+        match router.call(request).await {
+            Ok(response) => {
+                tracing::info!("{} {} -> {}", method, path, response.status());
+                let (parts, body) = response.into_parts();
+                Response::from_parts(parts, Full::new(Bytes::from(body.to_vec())))
+            }
+            Err(e) => plain_response(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()),
+        }
+    }
+
+    fn request_claims(&self, request: &Request<Body>) -> Result<Option<Value>, String> {
+        let bearer_token = request
+            .headers()
+            .get(AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            // API Gateway's Cognito authorizer expects the bare token, but
+            // also accept the common "Bearer" prefix.
+            .map(|v| v.strip_prefix("Bearer ").unwrap_or(v));
+        match (&self.jwt_secret, bearer_token) {
+            (Some(secret), Some(token)) => verify_jwt(token, secret).map(Some),
+            (Some(_), None) => Ok(None),
+            (None, _) => Ok(self.claims.clone()),
+        }
+    }
+}
+
+// Helper functions.
+// --------------------------------------------------
+
+// Verifies an HS256-signed JWT, returning its payload.
+fn verify_jwt(token: &str, secret: &str) -> Result<Value, String> {
+    let mut segments = token.split('.');
+    let (Some(header), Some(payload), Some(signature), None) = (
+        segments.next(),
+        segments.next(),
+        segments.next(),
+        segments.next(),
+    ) else {
+        return Err("malformed token".to_string());
+    };
+
+    let decode_json = |segment: &str| -> Result<Value, String> {
+        let bytes = BASE64_URL
+            .decode(segment)
+            .map_err(|_| "malformed token".to_string())?;
+        serde_json::from_slice(&bytes).map_err(|_| "malformed token".to_string())
+    };
+    if decode_json(header)?.get("alg").and_then(Value::as_str) != Some("HS256") {
+        return Err("unsupported token algorithm (expected HS256)".to_string());
+    }
+
+    let signature = BASE64_URL
+        .decode(signature)
+        .map_err(|_| "malformed token".to_string())?;
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .map_err(|_| "invalid JWT secret".to_string())?;
+    mac.update(format!("{}.{}", header, payload).as_bytes());
+    mac.verify_slice(&signature)
+        .map_err(|_| "invalid token signature".to_string())?;
+
+    let claims = decode_json(payload)?;
+    if let Some(exp) = claims.get("exp").and_then(Value::as_u64) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        if exp <= now {
+            return Err("token has expired".to_string());
+        }
+    }
+    Ok(claims)
+}
+
+fn plain_response(status: StatusCode, message: &str) -> Response<Full<Bytes>> {
+    let mut response = Response::new(Full::new(Bytes::from(message.to_string())));
+    *response.status_mut() = status;
+    response
+}
+
+// Tests.
+// --------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        auth::fake_cognito_claims,
+        request::RequestMetadata,
+        routing::{box_route_handler, AccessLevel, FunctionRoute},
+    };
+    use aws_lambda_events::apigw::{ApiGatewayProxyRequest, ApiGatewayProxyResponse};
+    use lambda_runtime::{Error, LambdaEvent};
+    use serde_json::json;
+
+    fn sign_jwt(claims: &Value, secret: &str) -> String {
+        let header = BASE64_URL.encode(json!({ "alg": "HS256", "typ": "JWT" }).to_string());
+        let payload = BASE64_URL.encode(claims.to_string());
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(format!("{}.{}", header, payload).as_bytes());
+        let signature = BASE64_URL.encode(mac.finalize().into_bytes());
+        format!("{}.{}.{}", header, payload, signature)
+    }
+
+    #[test]
+    fn test_verify_jwt() {
+        let claims = fake_cognito_claims("sub-1", "jane", &["admin"]);
+        let token = sign_jwt(&claims, "secret");
+        assert_eq!(verify_jwt(&token, "secret").unwrap(), claims);
+        assert_eq!(
+            verify_jwt(&token, "other").unwrap_err(),
+            "invalid token signature"
+        );
+        assert_eq!(
+            verify_jwt("not-a-token", "secret").unwrap_err(),
+            "malformed token"
+        );
+    }
+
+    #[test]
+    fn test_verify_expired_jwt() {
+        let token = sign_jwt(&json!({ "sub": "sub-1", "exp": 1 }), "secret");
+        assert_eq!(
+            verify_jwt(&token, "secret").unwrap_err(),
+            "token has expired"
+        );
+    }
+
+    async fn ping(
+        _: LambdaEvent<ApiGatewayProxyRequest>,
+        _: RequestMetadata,
+    ) -> Result<ApiGatewayProxyResponse, Error> {
+        crate::build_result("pong")
+    }
+
+    #[test]
+    fn test_request_claims() {
+        let claims = fake_cognito_claims("sub-1", "jane", &["admin"]);
+        let config = || {
+            RoutingConfig::builder()
+                .function(
+                    "ping",
+                    FunctionRoute::new(AccessLevel::Guest, box_route_handler(ping)),
+                )
+                .build()
+                .unwrap()
+        };
+        let request = |token: Option<&str>| {
+            let mut request = Request::builder();
+            if let Some(token) = token {
+                request = request.header(AUTHORIZATION, format!("Bearer {}", token));
+            }
+            request.body(Body::Empty).unwrap()
+        };
+
+        let server = LocalServer::new(config()).with_claims(claims.clone());
+        assert_eq!(
+            server.request_claims(&request(None)),
+            Ok(Some(claims.clone()))
+        );
+
+        // With a JWT secret, the fake claims are not used, and requests
+        // without a token are guest requests.
+        let server = server.with_jwt_secret("secret");
+        assert_eq!(server.request_claims(&request(None)), Ok(None));
+        let token = sign_jwt(&json!({ "sub": "sub-2" }), "secret");
+        assert_eq!(
+            server.request_claims(&request(Some(&token))),
+            Ok(Some(json!({ "sub": "sub-2" })))
+        );
+    }
+}
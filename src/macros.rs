@@ -29,22 +29,152 @@ macro_rules! aws_lambda_from_routing_config {
                 .without_time()
                 .init();
 
-            lambda_runtime::run(lambda_runtime::service_fn(|e| handle_route($config, e))).await
+            // The config is built once per cold start, and shared between
+            // invocations by the router.
+            lambda_runtime::run($crate::Router::new($config)).await
+        }
+    };
+}
+
+// Generates the main function of a small binary which prints the SAM template
+// fragment for the routing config (see SamTemplate), or writes it to the path
+// given as the first argument, e.g. in src/bin/sam_template.rs:
+//   sam_template_from_routing_config!(build_config(), SamTemplate::new("ApiFunction"));
+#[macro_export]
+macro_rules! sam_template_from_routing_config {
+    ($config:expr, $template:expr) => {
+        fn main() -> Result<(), Box<dyn std::error::Error>> {
+            let yaml = $template
+                .to_yaml(&$config)
+                .map_err(|e| format!("{:?}", e))?;
+            match std::env::args().nth(1) {
+                Some(path) => std::fs::write(path, yaml)?,
+                None => print!("{}", yaml),
+            }
+            Ok(())
         }
     };
 }
 
 #[macro_export]
 macro_rules! register_function_route {
+    // Variants with an async validator (see AsyncValidator), which receives
+    // the full request and optionally the shared state (a reference), e.g.:
+    //   register_function_route!(h, f, async is_team_member, ReqType, state: &*APP_STATE);
+    ($handler_name:ident, $func:ident, async $validator:expr, $request_data_type:ident $(, state: $state:expr)?) => {
+        pub async fn $handler_name(
+            event: lambda_runtime::LambdaEvent<aws_lambda_events::apigw::ApiGatewayProxyRequest>,
+            metadata: RequestMetadata,
+        ) -> Result<aws_lambda_events::apigw::ApiGatewayProxyResponse, lambda_runtime::Error> {
+            let obj = match $crate::parse_request_body!($request_data_type, &event.payload) {
+                Ok(obj) => obj,
+                Err(request_parsing_error) => return build_error(request_parsing_error),
+            };
+            if let Err(validation_error) = $crate::validate_request_data!(obj) {
+                return build_error(validation_error);
+            }
+            let validation = $crate::run_async_validator!(
+                $validator,
+                &event.payload,
+                &metadata,
+                &obj
+                $(, $state)?
+            );
+            match validation {
+                Ok(_) => match $func(obj).await {
+                    Ok(result) => $crate::IntoApiResponse::into_api_response(result),
+                    Err(func_error) => build_error(func_error),
+                },
+                Err(validation_error) => build_error(validation_error),
+            }
+        }
+    };
+    ($handler_name:ident, $func:ident, async $validator:expr $(, state: $state:expr)?) => {
+        pub async fn $handler_name(
+            event: lambda_runtime::LambdaEvent<aws_lambda_events::apigw::ApiGatewayProxyRequest>,
+            metadata: RequestMetadata,
+        ) -> Result<aws_lambda_events::apigw::ApiGatewayProxyResponse, lambda_runtime::Error> {
+            let validation = $crate::run_async_validator!(
+                $validator,
+                &event.payload,
+                &metadata,
+                &()
+                $(, $state)?
+            );
+            match validation {
+                Ok(_) => match $func().await {
+                    Ok(result) => $crate::IntoApiResponse::into_api_response(result),
+                    Err(func_error) => build_error(func_error),
+                },
+                Err(validation_error) => build_error(validation_error),
+            }
+        }
+    };
+    // Variants which also parse the query string and path parameters into
+    // typed structs (use () for either if not needed), passed to the function
+    // after the request data.
+    ($handler_name:ident, $func:ident, $validator:ident, $request_data_type:ident, query: $query_type:ty, path: $path_type:ty) => {
+        pub async fn $handler_name(
+            event: lambda_runtime::LambdaEvent<aws_lambda_events::apigw::ApiGatewayProxyRequest>,
+            metadata: RequestMetadata,
+        ) -> Result<aws_lambda_events::apigw::ApiGatewayProxyResponse, lambda_runtime::Error> {
+            let obj = match $crate::parse_request_body!($request_data_type, &event.payload) {
+                Ok(obj) => obj,
+                Err(request_parsing_error) => return build_error(request_parsing_error),
+            };
+            if let Err(validation_error) = $crate::validate_request_data!(obj) {
+                return build_error(validation_error);
+            }
+            let query = match parse_query::<$query_type>(&event.payload) {
+                Ok(query) => query,
+                Err(query_parsing_error) => return build_error(query_parsing_error),
+            };
+            let path = match parse_path_params::<$path_type>(&event.payload) {
+                Ok(path) => path,
+                Err(path_parsing_error) => return build_error(path_parsing_error),
+            };
+            match $validator(&obj, metadata) {
+                Ok(_) => match $func(obj, query, path).await {
+                    Ok(result) => $crate::IntoApiResponse::into_api_response(result),
+                    Err(func_error) => build_error(func_error),
+                },
+                Err(validation_error) => build_error(validation_error),
+            }
+        }
+    };
+    ($handler_name:ident, $func:ident, $validator:ident, query: $query_type:ty, path: $path_type:ty) => {
+        pub async fn $handler_name(
+            event: lambda_runtime::LambdaEvent<aws_lambda_events::apigw::ApiGatewayProxyRequest>,
+            metadata: RequestMetadata,
+        ) -> Result<aws_lambda_events::apigw::ApiGatewayProxyResponse, lambda_runtime::Error> {
+            let query = match parse_query::<$query_type>(&event.payload) {
+                Ok(query) => query,
+                Err(query_parsing_error) => return build_error(query_parsing_error),
+            };
+            let path = match parse_path_params::<$path_type>(&event.payload) {
+                Ok(path) => path,
+                Err(path_parsing_error) => return build_error(path_parsing_error),
+            };
+            match $validator(metadata) {
+                Ok(_) => match $func(query, path).await {
+                    Ok(result) => $crate::IntoApiResponse::into_api_response(result),
+                    Err(func_error) => build_error(func_error),
+                },
+                Err(validation_error) => build_error(validation_error),
+            }
+        }
+    };
     ($handler_name:ident, $func:ident, $validator:ident, $request_data_type:ident) => {
         pub async fn $handler_name(
             event: lambda_runtime::LambdaEvent<aws_lambda_events::apigw::ApiGatewayProxyRequest>,
             metadata: RequestMetadata,
         ) -> Result<aws_lambda_events::apigw::ApiGatewayProxyResponse, lambda_runtime::Error> {
-            match parse_request_data::<$request_data_type>(&event.payload) {
-                Ok(obj) => match $validator(&obj, metadata) {
+            match $crate::parse_request_body!($request_data_type, &event.payload) {
+                Ok(obj) => match $crate::validate_request_data!(obj)
+                    .and_then(|_| $validator(&obj, metadata))
+                {
                     Ok(_) => match $func(obj).await {
-                        Ok(result) => build_result(result),
+                        Ok(result) => $crate::IntoApiResponse::into_api_response(result),
                         Err(func_error) => build_error(func_error),
                     },
                     Err(validation_error) => build_error(validation_error),
@@ -60,7 +190,7 @@ macro_rules! register_function_route {
         ) -> Result<aws_lambda_events::apigw::ApiGatewayProxyResponse, lambda_runtime::Error> {
             match $validator(metadata) {
                 Ok(_) => match $func().await {
-                    Ok(result) => build_result(result),
+                    Ok(result) => $crate::IntoApiResponse::into_api_response(result),
                     Err(func_error) => build_error(func_error),
                 },
                 Err(validation_error) => build_error(validation_error),
@@ -98,3 +228,57 @@ macro_rules! register_crud_route_from_scaffolding {
         }
     };
 }
+
+// Runs the declarative validation rules of the request data if its type
+// implements Validate, otherwise evaluates to Ok(()).
+#[doc(hidden)]
+#[macro_export]
+macro_rules! validate_request_data {
+    ($obj:expr) => {{
+        #[allow(unused_imports)]
+        use $crate::{ValidateFallback as _, ValidateIfImplemented as _};
+        (&$crate::ValidationWrap(&$obj)).validate_if_implemented()
+    }};
+}
+
+// Parses the request data, with parse_multipart_form for MultipartForm and
+// parse_request_data for any other type.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! parse_request_body {
+    ($type:ty, $request:expr) => {{
+        #[allow(unused_imports)]
+        use $crate::{ParseFallback as _, ParseIfMultipartForm as _};
+        (&$crate::RequestDataWrap::<$type>(::std::marker::PhantomData)).parse($request)
+    }};
+}
+
+// Evaluates to the RequestSchema of the request data type (see schema.rs).
+#[macro_export]
+macro_rules! request_schema_of {
+    ($type:ty) => {{
+        #[allow(unused_imports)]
+        use $crate::{SchemaFallback as _, SchemaIfImplemented as _};
+        (&$crate::SchemaWrap::<$type>(::std::marker::PhantomData)).schema_if_implemented()
+    }};
+}
+
+// Builds the ValidationContext and awaits the async validator. The state
+// defaults to () if not provided.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! run_async_validator {
+    ($validator:expr, $request:expr, $metadata:expr, $data:expr) => {
+        $crate::run_async_validator!($validator, $request, $metadata, $data, &())
+    };
+    ($validator:expr, $request:expr, $metadata:expr, $data:expr, $state:expr) => {{
+        let validator = $validator;
+        let ctx = $crate::ValidationContext {
+            request: $request,
+            metadata: $metadata,
+            data: $data,
+            state: $state,
+        };
+        $crate::AsyncValidator::validate(&validator, &ctx).await
+    }};
+}
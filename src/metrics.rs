@@ -0,0 +1,218 @@
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    future::Future,
+    sync::atomic::{AtomicBool, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+
+// CloudWatch Embedded Metric Format (EMF) metrics.
+// --------------------------------------------------
+//
+// handle_route collects one metrics record per invocation, which is printed to
+// stdout as a single EMF JSON line at the end of the invocation. CloudWatch
+// extracts the metrics from the log line, so no metrics agent is needed.
+//
+// Handlers can add their own metrics and properties to the same record using
+// put_metric and set_metric_property, in which case they are reported with the
+// same dimensions (route, method, status, access level) as the built-in ones.
+//
+// The namespace is read from the AWS_EMF_NAMESPACE environment variable (the
+// same variable used by the official EMF libraries), falling back to the
+// function name.
+
+const DEFAULT_NAMESPACE: &str = "aws-embedded-metrics";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricUnit {
+    Seconds,
+    Milliseconds,
+    Bytes,
+    Kilobytes,
+    Megabytes,
+    Percent,
+    Count,
+    None,
+}
+
+impl MetricUnit {
+    fn as_str(&self) -> &'static str {
+        match self {
+            MetricUnit::Seconds => "Seconds",
+            MetricUnit::Milliseconds => "Milliseconds",
+            MetricUnit::Bytes => "Bytes",
+            MetricUnit::Kilobytes => "Kilobytes",
+            MetricUnit::Megabytes => "Megabytes",
+            MetricUnit::Percent => "Percent",
+            MetricUnit::Count => "Count",
+            MetricUnit::None => "None",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct MetricsRecord {
+    dimensions: Vec<(String, String)>,
+    metrics: BTreeMap<String, (MetricUnit, Vec<f64>)>,
+    properties: Map<String, Value>,
+}
+
+impl MetricsRecord {
+    pub(crate) fn put_dimension(&mut self, key: &str, value: &str) {
+        match self.dimensions.iter_mut().find(|(k, _)| k == key) {
+            Some((_, v)) => *v = value.to_string(),
+            None => self.dimensions.push((key.to_string(), value.to_string())),
+        }
+    }
+
+    pub(crate) fn put_metric(&mut self, name: &str, value: f64, unit: MetricUnit) {
+        self.metrics
+            .entry(name.to_string())
+            .or_insert_with(|| (unit, Vec::new()))
+            .1
+            .push(value);
+    }
+
+    pub(crate) fn set_property(&mut self, key: &str, value: Value) {
+        self.properties.insert(key.to_string(), value);
+    }
+
+    pub(crate) fn to_emf(&self, namespace: &str, timestamp_ms: u128) -> Value {
+        let mut root = self.properties.clone();
+        for (key, value) in &self.dimensions {
+            root.insert(key.clone(), Value::String(value.clone()));
+        }
+        for (name, (_, values)) in &self.metrics {
+            root.insert(
+                name.clone(),
+                match values.as_slice() {
+                    [value] => json!(value),
+                    values => json!(values),
+                },
+            );
+        }
+        root.insert(
+            "_aws".to_string(),
+            json!({
+                "Timestamp": timestamp_ms as u64,
+                "CloudWatchMetrics": [{
+                    "Namespace": namespace,
+                    "Dimensions": [self.dimensions.iter().map(|(k, _)| k).collect::<Vec<_>>()],
+                    "Metrics": self.metrics.iter().map(|(name, (unit, _))| json!({
+                        "Name": name,
+                        "Unit": unit.as_str(),
+                    })).collect::<Vec<_>>(),
+                }],
+            }),
+        );
+        Value::Object(root)
+    }
+}
+
+tokio::task_local! {
+    static CURRENT_METRICS: RefCell<MetricsRecord>;
+}
+
+static COLD_START: AtomicBool = AtomicBool::new(true);
+
+// Adds a custom metric to the record of the request currently being handled
+// by handle_route. Does nothing if called outside of handle_route.
+pub fn put_metric(name: &str, value: f64, unit: MetricUnit) {
+    record_metrics(|m| m.put_metric(name, value, unit));
+}
+
+// Adds a property (searchable in CloudWatch Logs Insights, but not a metric
+// or dimension) to the record of the request currently being handled.
+pub fn set_metric_property(key: &str, value: impl Serialize) {
+    if let Ok(value) = serde_json::to_value(value) {
+        record_metrics(|m| m.set_property(key, value));
+    }
+}
+
+pub(crate) fn record_metrics(f: impl FnOnce(&mut MetricsRecord)) {
+    let _ = CURRENT_METRICS.try_with(|m| f(&mut m.borrow_mut()));
+}
+
+// Runs the future with a fresh metrics record in scope, returning the record
+// alongside the output so that it can be flushed by the caller.
+pub(crate) async fn with_metrics<F: Future>(f: F) -> (F::Output, MetricsRecord) {
+    CURRENT_METRICS
+        .scope(RefCell::new(MetricsRecord::default()), async {
+            let output = f.await;
+            let record = CURRENT_METRICS.with(|m| m.take());
+            (output, record)
+        })
+        .await
+}
+
+// Returns true only for the first invocation handled by this container.
+pub(crate) fn take_cold_start() -> bool {
+    COLD_START.swap(false, Ordering::Relaxed)
+}
+
+pub(crate) fn flush_metrics(record: &MetricsRecord) {
+    let namespace = std::env::var("AWS_EMF_NAMESPACE")
+        .or_else(|_| std::env::var("AWS_LAMBDA_FUNCTION_NAME"))
+        .unwrap_or_else(|_| DEFAULT_NAMESPACE.to_string());
+    let timestamp_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or_default();
+    // Printed directly (rather than through tracing) since CloudWatch only
+    // recognizes EMF records if the log line is exactly the JSON object.
+    println!("{}", record.to_emf(&namespace, timestamp_ms));
+}
+
+// Tests.
+// --------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_emf() {
+        let mut record = MetricsRecord::default();
+        record.put_dimension("Route", "orders/create");
+        record.put_dimension("Status", "200");
+        record.put_dimension("Status", "500");
+        record.put_metric("Latency", 12.5, MetricUnit::Milliseconds);
+        record.put_metric("ItemsWritten", 1.0, MetricUnit::Count);
+        record.put_metric("ItemsWritten", 2.0, MetricUnit::Count);
+        record.set_property("OrderId", json!("abc"));
+
+        let emf = record.to_emf("TestNamespace", 1000);
+        let directive = &emf["_aws"]["CloudWatchMetrics"][0];
+        assert_eq!(emf["_aws"]["Timestamp"], json!(1000));
+        assert_eq!(directive["Namespace"], json!("TestNamespace"));
+        assert_eq!(directive["Dimensions"], json!([["Route", "Status"]]));
+        assert_eq!(
+            directive["Metrics"],
+            json!([
+                { "Name": "ItemsWritten", "Unit": "Count" },
+                { "Name": "Latency", "Unit": "Milliseconds" },
+            ])
+        );
+        assert_eq!(emf["Route"], json!("orders/create"));
+        assert_eq!(emf["Status"], json!("500"));
+        assert_eq!(emf["Latency"], json!(12.5));
+        assert_eq!(emf["ItemsWritten"], json!([1.0, 2.0]));
+        assert_eq!(emf["OrderId"], json!("abc"));
+    }
+
+    #[tokio::test]
+    async fn test_put_metric_in_scope() {
+        put_metric("Ignored", 1.0, MetricUnit::Count);
+        let (_, record) = with_metrics(async {
+            put_metric("Custom", 3.0, MetricUnit::Count);
+            set_metric_property("Key", "value");
+        })
+        .await;
+        assert_eq!(record.metrics["Custom"], (MetricUnit::Count, vec![3.0]));
+        assert_eq!(record.properties["Key"], json!("value"));
+        assert!(!record.metrics.contains_key("Ignored"));
+    }
+}
@@ -0,0 +1,689 @@
+use std::{collections::HashMap, sync::Arc};
+
+use aws_lambda_events::http::{header::CONTENT_TYPE, HeaderValue};
+use fractic_server_error::ServerError;
+use schemars::{
+    gen::{SchemaGenerator, SchemaSettings},
+    schema::Schema,
+    JsonSchema,
+};
+use serde_json::{json, Map, Value};
+
+use crate::{
+    body::RequestBodyConfig,
+    builder::{normalize_path, RoutingConfigBuilder},
+    constants::{INTERNAL_SERVER_ERROR_MSG, UNAUTHORIZED_ERROR_MSG},
+    errors::InvalidOpenApiDocsError,
+    response::build_simple,
+    routing::{AccessLevel, CrudRoute, RoutingConfig},
+    schema::RequestSchema,
+};
+
+// OpenAPI document generation.
+// --------------------------------------------------
+//
+// Generates an OpenAPI 3.1 document for a RoutingConfig (requires the
+// 'openapi' feature), for example:
+//
+//   let document = OpenApi::new("Orders API", "1.0.0")
+//       .route(
+//           "orders/create",
+//           RouteDocs::new()
+//               .summary("Create an order")
+//               .response::<Order>(),
+//       )
+//       .generate(&config)?;
+//
+// All routes of the config are included, documented or not. Function routes
+// are documented as POST operations, and CRUD routes as POST / GET / PUT /
+// DELETE operations (skipping methods with AccessLevel::None). Request and
+// response schemas are generated with schemars, with responses wrapped in the
+// ResponseWrapper envelope. The request schema of function routes is captured
+// from the request data type when the route is registered (see schema.rs), and
+// can be overridden with RouteDocs::request. Security requirements follow the
+// access level: guest routes accept optional authentication, while user and
+// admin routes require the Cognito token (admin routes are additionally marked
+// with 'x-access-level: admin', since group membership cannot be expressed in
+// OpenAPI).
+//
+// The document can also be served by the API itself, see
+// RoutingConfigBuilder::openapi.
+
+const SECURITY_SCHEME: &str = "cognito";
+const ERROR_RESPONSE_SCHEMA: &str = "ErrorResponse";
+
+pub(crate) type SchemaFn = fn(&mut SchemaGenerator) -> Schema;
+
+#[derive(Default, Clone)]
+pub struct RouteDocs {
+    summary: Option<String>,
+    description: Option<String>,
+    tags: Vec<String>,
+    // Schema of the request data, overriding the schema captured when the
+    // route was registered (see schema.rs). For CRUD routes, applies to create
+    // and update requests.
+    request: Option<SchemaFn>,
+    // Schema of the 'data' field of successful responses.
+    response: Option<SchemaFn>,
+    // Name and whether it is required, optionally restricted to one method
+    // (lowercase, as in the document).
+    query_params: Vec<(Option<&'static str>, String, bool)>,
+}
+
+impl RouteDocs {
+    pub fn new() -> Self {
+        RouteDocs::default()
+    }
+
+    pub fn summary(mut self, summary: &str) -> Self {
+        self.summary = Some(summary.to_string());
+        self
+    }
+
+    pub fn description(mut self, description: &str) -> Self {
+        self.description = Some(description.to_string());
+        self
+    }
+
+    pub fn tag(mut self, tag: &str) -> Self {
+        self.tags.push(tag.to_string());
+        self
+    }
+
+    // Query parameters of CRUD routes using CrudRouteScaffolding.
+    pub fn crud_scaffolding() -> Self {
+        RouteDocs {
+            query_params: vec![
+                (Some("post"), "parent_id".to_string(), true),
+                (Some("get"), "id".to_string(), true),
+                (Some("delete"), "id".to_string(), true),
+            ],
+            ..Default::default()
+        }
+    }
+
+    pub fn query_param(mut self, name: &str, required: bool) -> Self {
+        self.query_params.push((None, name.to_string(), required));
+        self
+    }
+
+    pub fn request<T: JsonSchema>(mut self) -> Self {
+        self.request = Some(|gen| gen.subschema_for::<T>());
+        self
+    }
+
+    pub fn response<T: JsonSchema>(mut self) -> Self {
+        self.response = Some(|gen| gen.subschema_for::<T>());
+        self
+    }
+}
+
+#[derive(Clone)]
+pub struct OpenApi {
+    title: String,
+    version: String,
+    description: Option<String>,
+    servers: Vec<String>,
+    routes: Vec<(String, RouteDocs)>,
+}
+
+impl OpenApi {
+    pub fn new(title: &str, version: &str) -> Self {
+        OpenApi {
+            title: title.to_string(),
+            version: version.to_string(),
+            description: None,
+            servers: Vec::new(),
+            routes: Vec::new(),
+        }
+    }
+
+    pub fn description(mut self, description: &str) -> Self {
+        self.description = Some(description.to_string());
+        self
+    }
+
+    // Base URL of the API (for example the API Gateway stage URL).
+    pub fn server(mut self, url: &str) -> Self {
+        self.servers.push(url.to_string());
+        self
+    }
+
+    pub fn route(mut self, path: &str, docs: RouteDocs) -> Self {
+        self.routes.push((path.to_string(), docs));
+        self
+    }
+
+    // Fails if any documented route does not exist in the config.
+    pub fn generate(&self, config: &RoutingConfig) -> Result<Value, ServerError> {
+        self.build_document(config)
+            .map_err(|problems| InvalidOpenApiDocsError::new(&problems.join("; ")))
+    }
+
+    fn build_document(&self, config: &RoutingConfig) -> Result<Value, Vec<String>> {
+        let mut problems = Vec::new();
+        let mut docs = HashMap::new();
+        for (path, route_docs) in &self.routes {
+            let normalized = normalize_path(path);
+            if !config.function_routes().contains_key(&normalized)
+                && !config.crud_routes().contains_key(&normalized)
+            {
+                problems.push(format!("documented route '{}' does not exist", normalized));
+            } else if docs.insert(normalized.clone(), route_docs).is_some() {
+                problems.push(format!(
+                    "route '{}' is documented more than once",
+                    normalized
+                ));
+            }
+        }
+        if !problems.is_empty() {
+            return Err(problems);
+        }
+
+        let mut gen = SchemaSettings::draft2019_09()
+            .with(|settings| settings.definitions_path = "#/components/schemas/".to_string())
+            .into_generator();
+        let mut paths = Map::new();
+        for (path, route) in config.function_routes() {
+            let operations = [("post", operation_id(path, None), route.access_level, true)];
+            let item = path_item(
+                &mut gen,
+                &operations,
+                &route.body_config,
+                route.request_schema,
+                docs.get(path).copied(),
+            );
+            paths.insert(format!("/{}", path), item);
+        }
+        for (path, route) in config.crud_routes() {
+            let operations = [
+                (
+                    "post",
+                    operation_id(path, Some("create")),
+                    route.create_access_level,
+                    true,
+                ),
+                (
+                    "get",
+                    operation_id(path, Some("read")),
+                    route.read_access_level,
+                    false,
+                ),
+                (
+                    "put",
+                    operation_id(path, Some("update")),
+                    route.update_access_level,
+                    true,
+                ),
+                (
+                    "delete",
+                    operation_id(path, Some("delete")),
+                    route.delete_access_level,
+                    false,
+                ),
+            ];
+            let item = path_item(
+                &mut gen,
+                &operations,
+                &route.body_config,
+                RequestSchema::none(),
+                docs.get(path).copied(),
+            );
+            paths.insert(format!("/{}", path), item);
+        }
+
+        let mut schemas: Map<String, Value> = gen
+            .take_definitions()
+            .into_iter()
+            .map(|(name, schema)| (name, serde_json::to_value(schema).unwrap_or_default()))
+            .collect();
+        schemas.insert(
+            ERROR_RESPONSE_SCHEMA.to_string(),
+            json!({
+                "type": "object",
+                "description": "Error forwarded to the client. The message is safe to show to the user.",
+                "required": ["ok", "error"],
+                "properties": {
+                    "ok": { "const": false },
+                    "data": { "type": "null" },
+                    "error": { "type": "string" },
+                    "request_id": {
+                        "type": "string",
+                        "description": "ID of the request, to include in bug reports.",
+                    },
+                },
+            }),
+        );
+
+        let mut info = json!({ "title": self.title, "version": self.version });
+        if let Some(description) = &self.description {
+            info["description"] = description.clone().into();
+        }
+        let mut document = json!({
+            "openapi": "3.1.0",
+            "info": info,
+            "paths": paths,
+            "components": {
+                "schemas": schemas,
+                "securitySchemes": {
+                    // API Gateway's Cognito authorizer expects the bare
+                    // token, not a bearer scheme.
+                    SECURITY_SCHEME: {
+                        "type": "apiKey",
+                        "in": "header",
+                        "name": "Authorization",
+                        "description": "Cognito user pool token.",
+                    },
+                },
+            },
+        });
+        if !self.servers.is_empty() {
+            document["servers"] = self
+                .servers
+                .iter()
+                .map(|url| json!({ "url": url }))
+                .collect();
+        }
+        Ok(document)
+    }
+}
+
+impl RoutingConfigBuilder {
+    // Serves the OpenAPI document of the built config as a guest-accessible
+    // GET route at the given path. The route itself is not included in the
+    // document.
+    pub fn openapi(mut self, path: &str, openapi: OpenApi) -> Self {
+        self.openapi = Some((path.to_string(), openapi));
+        self
+    }
+}
+
+// Generates the document and adds the route serving it to the config. Called
+// by RoutingConfigBuilder::build, which reports the returned problems.
+pub(crate) fn add_openapi_route(
+    config: &mut RoutingConfig,
+    path: &str,
+    openapi: &OpenApi,
+) -> Result<(), Vec<String>> {
+    let normalized = normalize_path(path);
+    if normalized.is_empty() {
+        return Err(vec![format!("OpenAPI route path '{}' is empty", path)]);
+    }
+    if config.function_routes.contains_key(&normalized)
+        || config.crud_routes.contains_key(&normalized)
+    {
+        return Err(vec![format!(
+            "OpenAPI route '{}' conflicts with an existing route",
+            normalized
+        )]);
+    }
+    let document = Arc::new(openapi.build_document(config)?.to_string());
+    config.crud_routes.insert(
+        normalized,
+        CrudRoute {
+            create_access_level: AccessLevel::None,
+            read_access_level: AccessLevel::Guest,
+            update_access_level: AccessLevel::None,
+            delete_access_level: AccessLevel::None,
+            handler: Box::new(move |_, _| {
+                let document = document.clone();
+                Box::pin(async move {
+                    let mut response = build_simple(document.as_str().to_string());
+                    response
+                        .headers
+                        .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+                    Ok(response)
+                })
+            }),
+            timeout: None,
+            body_config: Default::default(),
+            field_selection: Default::default(),
+            // Only changes on deployment.
+            cache_control: Some("public, max-age=300".to_string()),
+        },
+    );
+    Ok(())
+}
+
+// Helper functions.
+// --------------------------------------------------
+
+// HTTP method, operation ID, access level and whether the request has a body.
+type OperationSpec = (&'static str, String, AccessLevel, bool);
+
+fn path_item(
+    gen: &mut SchemaGenerator,
+    operations: &[OperationSpec],
+    body_config: &RequestBodyConfig,
+    request_schema: RequestSchema,
+    docs: Option<&RouteDocs>,
+) -> Value {
+    let mut item = Map::new();
+    for (method, operation_id, access_level, has_body) in operations {
+        let security = match access_level {
+            AccessLevel::Guest => json!([{}, { SECURITY_SCHEME: [] }]),
+            AccessLevel::User | AccessLevel::Admin => json!([{ SECURITY_SCHEME: [] }]),
+            // Not accessible (the builder rejects unresolved Inherit).
+            AccessLevel::None | AccessLevel::Inherit => continue,
+        };
+        let mut operation = json!({
+            "operationId": operation_id,
+            "security": security,
+            "responses": responses(gen, access_level, body_config, *has_body, docs),
+        });
+        if *access_level == AccessLevel::Admin {
+            operation["x-access-level"] = "admin".into();
+        }
+        if let Some(docs) = docs {
+            if let Some(summary) = &docs.summary {
+                operation["summary"] = summary.clone().into();
+            }
+            if let Some(description) = &docs.description {
+                operation["description"] = description.clone().into();
+            }
+            if !docs.tags.is_empty() {
+                operation["tags"] = docs.tags.clone().into();
+            }
+            let parameters: Vec<Value> = docs
+                .query_params
+                .iter()
+                .filter(|(only_method, _, _)| match only_method {
+                    Some(only_method) => only_method == method,
+                    None => true,
+                })
+                .map(|(_, name, required)| {
+                    json!({
+                        "name": name,
+                        "in": "query",
+                        "required": required,
+                        "schema": { "type": "string" },
+                    })
+                })
+                .collect();
+            if !parameters.is_empty() {
+                operation["parameters"] = parameters.into();
+            }
+        }
+        if *has_body {
+            let schema = match docs
+                .and_then(|docs| docs.request)
+                .or(request_schema.schema_fn)
+            {
+                Some(schema_fn) => serde_json::to_value(schema_fn(gen)).unwrap_or_default(),
+                None => json!({}),
+            };
+            let content_types = match body_config.content_types.is_empty() {
+                true => vec!["application/json".to_string()],
+                false => body_config.content_types.clone(),
+            };
+            operation["requestBody"] = json!({
+                "content": content_types
+                    .into_iter()
+                    .map(|content_type| (content_type, json!({ "schema": schema.clone() })))
+                    .collect::<Map<String, Value>>(),
+            });
+        }
+        item.insert(method.to_string(), operation);
+    }
+    Value::Object(item)
+}
+
+fn responses(
+    gen: &mut SchemaGenerator,
+    access_level: &AccessLevel,
+    body_config: &RequestBodyConfig,
+    has_body: bool,
+    docs: Option<&RouteDocs>,
+) -> Value {
+    let data_schema = match docs.and_then(|docs| docs.response) {
+        Some(schema_fn) => serde_json::to_value(schema_fn(gen)).unwrap_or_default(),
+        None => json!({}),
+    };
+    let error_ref = json!({ "$ref": format!("#/components/schemas/{}", ERROR_RESPONSE_SCHEMA) });
+    let wrapped_error = |description: &str| {
+        json!({
+            "description": description,
+            "content": { "application/json": { "schema": error_ref } },
+        })
+    };
+    let plain_text = |description: &str, example: &str| {
+        json!({
+            "description": description,
+            "content": { "text/plain": { "schema": { "type": "string" }, "example": example } },
+        })
+    };
+
+    let mut responses = json!({
+        // Errors forwarded to the client are also returned with status 200,
+        // so that clients such as Amplify do not treat them as server errors.
+        "200": {
+            "description": "Response data if 'ok' is true, otherwise an error safe to show to the user.",
+            "content": {
+                "application/json": {
+                    "schema": {
+                        "oneOf": [
+                            {
+                                "type": "object",
+                                "required": ["ok", "data"],
+                                "properties": {
+                                    "ok": { "const": true },
+                                    "data": data_schema,
+                                    "error": { "type": "null" },
+                                },
+                            },
+                            error_ref,
+                        ],
+                    },
+                },
+            },
+        },
+        "500": plain_text("Unexpected server error.", INTERNAL_SERVER_ERROR_MSG),
+    });
+    if *access_level != AccessLevel::Guest {
+        responses["401"] = plain_text(
+            "Not authenticated, or not authorized to access the route.",
+            UNAUTHORIZED_ERROR_MSG,
+        );
+    }
+    if has_body {
+        responses["400"] = wrapped_error("Request body could not be decoded.");
+        if body_config.max_body_size.is_some() || body_config.max_part_size.is_some() {
+            responses["413"] = wrapped_error("Request body is too large.");
+        }
+        if !body_config.content_types.is_empty() {
+            responses["415"] = wrapped_error("Unsupported request content type.");
+        }
+    }
+    responses
+}
+
+fn operation_id(path: &str, action: Option<&str>) -> String {
+    let base = path.replace(['/', '-', '.'], "_");
+    match action {
+        Some(action) => format!("{}_{}", base, action),
+        None => base,
+    }
+}
+
+// Tests.
+// --------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        build_error, register_function_route,
+        request::RequestMetadata,
+        route,
+        routing::{box_route_handler, FunctionRoute},
+        testing::{ok_handler, TestClient, TestRequest},
+    };
+    use serde::Deserialize;
+
+    #[allow(dead_code)]
+    #[derive(Deserialize, JsonSchema)]
+    struct CreateOrderRequest {
+        item: String,
+        quantity: u32,
+    }
+
+    #[allow(dead_code)]
+    #[derive(Deserialize)]
+    struct UndocumentedRequest {
+        item: String,
+    }
+
+    async fn create_order(_: CreateOrderRequest) -> Result<String, ServerError> {
+        Ok("created".to_string())
+    }
+
+    fn allow_all(_: &CreateOrderRequest, _: RequestMetadata) -> Result<(), ServerError> {
+        Ok(())
+    }
+
+    register_function_route!(
+        create_order_handler,
+        create_order,
+        allow_all,
+        CreateOrderRequest
+    );
+
+    #[route(post, "test/openapi/create")]
+    async fn registered_create_order(_: CreateOrderRequest) -> Result<String, ServerError> {
+        Ok("created".to_string())
+    }
+
+    #[allow(dead_code)]
+    #[derive(JsonSchema)]
+    struct Order {
+        id: String,
+    }
+
+    fn create_builder() -> RoutingConfigBuilder {
+        RoutingConfig::builder()
+            .function(
+                "orders/create",
+                FunctionRoute::new(AccessLevel::User, box_route_handler(create_order_handler))
+                    .with_body_config(RequestBodyConfig::json().with_max_body_size(1024))
+                    .with_request_schema(crate::request_schema_of!(CreateOrderRequest)),
+            )
+            .crud(
+                "items",
+                CrudRoute {
+                    create_access_level: AccessLevel::Admin,
+                    read_access_level: AccessLevel::Guest,
+                    update_access_level: AccessLevel::Admin,
+                    delete_access_level: AccessLevel::None,
+                    handler: box_route_handler(ok_handler),
+                    timeout: None,
+                    body_config: Default::default(),
+                    field_selection: Default::default(),
+                    cache_control: None,
+                },
+            )
+    }
+
+    fn create_openapi() -> OpenApi {
+        OpenApi::new("Orders API", "1.0.0")
+            .route(
+                "/orders/create/",
+                RouteDocs::new()
+                    .summary("Create an order")
+                    .response::<Order>(),
+            )
+            .route("items", RouteDocs::crud_scaffolding())
+    }
+
+    #[test]
+    fn test_generate() {
+        let document = create_openapi()
+            .generate(&create_builder().build().unwrap())
+            .unwrap();
+        let create = &document["paths"]["/orders/create"]["post"];
+        assert_eq!(create["summary"], "Create an order");
+        assert_eq!(create["security"], json!([{ "cognito": [] }]));
+        assert_eq!(
+            document["components"]["securitySchemes"]["cognito"],
+            json!({
+                "type": "apiKey",
+                "in": "header",
+                "name": "Authorization",
+                "description": "Cognito user pool token.",
+            })
+        );
+        assert_eq!(
+            create["requestBody"]["content"]["application/json"]["schema"]["$ref"],
+            "#/components/schemas/CreateOrderRequest"
+        );
+        assert_eq!(
+            create["responses"]["200"]["content"]["application/json"]["schema"]["oneOf"][0]
+                ["properties"]["data"]["$ref"],
+            "#/components/schemas/Order"
+        );
+        assert!(create["responses"].get("413").is_some());
+        assert!(document["components"]["schemas"]["CreateOrderRequest"].is_object());
+
+        let items = &document["paths"]["/items"];
+        assert_eq!(items["get"]["security"], json!([{}, { "cognito": [] }]));
+        assert!(items["get"].get("requestBody").is_none());
+        assert_eq!(items["put"]["x-access-level"], "admin");
+        assert!(items.get("delete").is_none());
+        assert_eq!(items["get"]["parameters"][0]["name"], "id");
+        assert!(items["put"].get("parameters").is_none());
+    }
+
+    #[test]
+    fn test_request_schema() {
+        // Captured by #[route].
+        let config = RoutingConfig::from_registered_routes().unwrap();
+        let document = OpenApi::new("Orders API", "1.0.0")
+            .generate(&config)
+            .unwrap();
+        assert_eq!(
+            document["paths"]["/test/openapi/create"]["post"]["requestBody"]["content"]
+                ["application/json"]["schema"]["$ref"],
+            "#/components/schemas/CreateOrderRequest"
+        );
+
+        // Overridden by RouteDocs.
+        let document = OpenApi::new("Orders API", "1.0.0")
+            .route("orders/create", RouteDocs::new().request::<Order>())
+            .generate(&create_builder().build().unwrap())
+            .unwrap();
+        assert_eq!(
+            document["paths"]["/orders/create"]["post"]["requestBody"]["content"]
+                ["application/json"]["schema"]["$ref"],
+            "#/components/schemas/Order"
+        );
+
+        // Types without JsonSchema are not captured.
+        assert!(crate::request_schema_of!(UndocumentedRequest)
+            .schema_fn
+            .is_none());
+    }
+
+    #[test]
+    fn test_unknown_route_rejected() {
+        let err = OpenApi::new("Orders API", "1.0.0")
+            .route("orders/missing", RouteDocs::new())
+            .generate(&create_builder().build().unwrap())
+            .unwrap_err();
+        assert!(format!("{:?}", err).contains("documented route 'orders/missing' does not exist"));
+    }
+
+    #[tokio::test]
+    async fn test_served_document() {
+        let config = create_builder()
+            .openapi("openapi.json", create_openapi())
+            .build()
+            .unwrap();
+        let response = TestClient::new(config)
+            .send(TestRequest::get("openapi.json"))
+            .await;
+        response.assert_status(200);
+        assert_eq!(response.header("content-type"), Some("application/json"));
+        let document: Value = serde_json::from_str(&response.text()).unwrap();
+        assert_eq!(document["info"]["title"], "Orders API");
+        assert!(document["paths"].get("/openapi.json").is_none());
+    }
+}
@@ -0,0 +1,314 @@
+use std::collections::BTreeMap;
+
+use aws_lambda_events::apigw::ApiGatewayProxyRequest;
+use fractic_server_error::ServerError;
+use serde::de::{
+    self,
+    value::{Error as ValueError, MapDeserializer, SeqDeserializer},
+    DeserializeOwned, Deserializer, IntoDeserializer, Visitor,
+};
+
+use crate::errors::InvalidRequestError;
+
+// API Gateway query string and path parameter utils.
+// --------------------------------------------------
+
+// Parses the query string parameters into a typed struct. Since all values
+// are strings, they are converted on demand to the type expected by each
+// field (numbers, booleans, unit enum variants, etc.). Repeated keys can be
+// collected into a Vec, and missing keys into an Option.
+pub fn parse_query<T>(request: &ApiGatewayProxyRequest) -> Result<T, ServerError>
+where
+    T: DeserializeOwned,
+{
+    // The multi-value map contains all values of repeated keys, whereas the
+    // single-value map only contains the last one, so prefer it if present.
+    let source = if request.multi_value_query_string_parameters.is_empty() {
+        &request.query_string_parameters
+    } else {
+        &request.multi_value_query_string_parameters
+    };
+    let mut params: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (key, value) in source.iter() {
+        params
+            .entry(key.to_string())
+            .or_default()
+            .push(value.to_string());
+    }
+    deserialize_params(params, "query parameters")
+}
+
+// Parses the path parameters into a typed struct, with the same conversion
+// rules as parse_query.
+pub fn parse_path_params<T>(request: &ApiGatewayProxyRequest) -> Result<T, ServerError>
+where
+    T: DeserializeOwned,
+{
+    let params = request
+        .path_parameters
+        .iter()
+        .map(|(k, v)| (k.clone(), vec![v.clone()]))
+        .collect();
+    deserialize_params(params, "path parameters")
+}
+
+// Helper functions.
+// --------------------------------------------------
+
+pub(crate) fn deserialize_params<T>(
+    params: BTreeMap<String, Vec<String>>,
+    kind: &str,
+) -> Result<T, ServerError>
+where
+    T: DeserializeOwned,
+{
+    // Wrap the deserializer to track the path of the field that failed to
+    // parse, similar to serde_json_path_to_error for request bodies.
+    serde_path_to_error::deserialize(ParamsDeserializer(params)).map_err(|e| {
+        InvalidRequestError::with_debug(
+            &format!("invalid {} (field '{}')", kind, e.path()),
+            &e.inner().to_string(),
+        )
+    })
+}
+
+// Deserializes a map of parameter name to its (possibly repeated) values.
+struct ParamsDeserializer(BTreeMap<String, Vec<String>>);
+
+impl<'de> Deserializer<'de> for ParamsDeserializer {
+    type Error = ValueError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_map(MapDeserializer::new(
+            self.0.into_iter().map(|(k, v)| (k, ParamValue(v))),
+        ))
+    }
+
+    // Allows using () for routes which do not use the parameters.
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+// Deserializes the values of a single parameter.
+struct ParamValue(Vec<String>);
+
+impl ParamValue {
+    fn first(&self) -> &str {
+        self.0.first().map(String::as_str).unwrap_or_default()
+    }
+
+    fn parse<T: std::str::FromStr>(&self, expected: &str) -> Result<T, ValueError> {
+        self.first()
+            .parse()
+            .map_err(|_| de::Error::invalid_value(de::Unexpected::Str(self.first()), &expected))
+    }
+}
+
+impl<'de> IntoDeserializer<'de, ValueError> for ParamValue {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident: $type:ty, $expected:literal;)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+                visitor.$visit(self.parse::<$type>($expected)?)
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for ParamValue {
+    type Error = ValueError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0.len() {
+            1 => visitor.visit_string(self.0.into_iter().next().unwrap_or_default()),
+            _ => self.deserialize_seq(visitor),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.first() {
+            "true" | "1" => visitor.visit_bool(true),
+            "false" | "0" => visitor.visit_bool(false),
+            other => Err(de::Error::invalid_value(
+                de::Unexpected::Str(other),
+                &"a boolean",
+            )),
+        }
+    }
+
+    deserialize_parsed! {
+        deserialize_i8 => visit_i8: i8, "an integer";
+        deserialize_i16 => visit_i16: i16, "an integer";
+        deserialize_i32 => visit_i32: i32, "an integer";
+        deserialize_i64 => visit_i64: i64, "an integer";
+        deserialize_u8 => visit_u8: u8, "a non-negative integer";
+        deserialize_u16 => visit_u16: u16, "a non-negative integer";
+        deserialize_u32 => visit_u32: u32, "a non-negative integer";
+        deserialize_u64 => visit_u64: u64, "a non-negative integer";
+        deserialize_f32 => visit_f32: f32, "a number";
+        deserialize_f64 => visit_f64: f64, "a number";
+        deserialize_char => visit_char: char, "a single character";
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        if self.0.is_empty() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_seq(SeqDeserializer::new(
+            self.0.into_iter().map(|v| ParamValue(vec![v])),
+        ))
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        let value: String = self.first().to_string();
+        visitor.visit_enum(value.into_deserializer())
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_string(self.first().to_string())
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_string(visitor)
+    }
+
+    serde::forward_to_deserialize_any! {
+        i128 u128 bytes byte_buf unit unit_struct tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+// Tests.
+// --------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    #[serde(rename_all = "snake_case")]
+    enum Order {
+        Newest,
+        Oldest,
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct TestQuery {
+        limit: u32,
+        include_deleted: bool,
+        order: Order,
+        tags: Vec<String>,
+        cursor: Option<String>,
+    }
+
+    fn create_request(params: &[(&str, &[&str])]) -> ApiGatewayProxyRequest {
+        let map: HashMap<String, Vec<String>> = params
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.iter().map(|v| v.to_string()).collect()))
+            .collect();
+        ApiGatewayProxyRequest {
+            multi_value_query_string_parameters: map.into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_query() {
+        let request = create_request(&[
+            ("limit", &["20"]),
+            ("include_deleted", &["true"]),
+            ("order", &["oldest"]),
+            ("tags", &["a", "b"]),
+        ]);
+        assert_eq!(
+            parse_query::<TestQuery>(&request).unwrap(),
+            TestQuery {
+                limit: 20,
+                include_deleted: true,
+                order: Order::Oldest,
+                tags: vec!["a".to_string(), "b".to_string()],
+                cursor: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_query_single_value_vec() {
+        let request = create_request(&[
+            ("limit", &["1"]),
+            ("include_deleted", &["0"]),
+            ("order", &["newest"]),
+            ("tags", &["a"]),
+            ("cursor", &["abc"]),
+        ]);
+        let query = parse_query::<TestQuery>(&request).unwrap();
+        assert_eq!(query.tags, vec!["a".to_string()]);
+        assert_eq!(query.cursor, Some("abc".to_string()));
+    }
+
+    #[test]
+    fn test_parse_query_invalid_number_names_field() {
+        let request = create_request(&[
+            ("limit", &["many"]),
+            ("include_deleted", &["true"]),
+            ("order", &["newest"]),
+            ("tags", &["a"]),
+        ]);
+        let err = format!("{:?}", parse_query::<TestQuery>(&request).unwrap_err());
+        assert!(err.contains("InvalidRequestError"));
+        assert!(err.contains("limit"));
+    }
+
+    #[test]
+    fn test_parse_path_params() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct TestPath {
+            proxy: String,
+        }
+        let request = ApiGatewayProxyRequest {
+            path_parameters: [("proxy".to_string(), "orders/create".to_string())].into(),
+            ..Default::default()
+        };
+        assert_eq!(
+            parse_path_params::<TestPath>(&request).unwrap(),
+            TestPath {
+                proxy: "orders/create".to_string()
+            }
+        );
+        assert!(parse_path_params::<()>(&request).is_ok());
+    }
+}
@@ -0,0 +1,197 @@
+use std::time::Duration;
+
+use aws_lambda_events::apigw::ApiGatewayProxyRequest;
+use fractic_server_error::ServerError;
+use lambda_runtime::LambdaEvent;
+
+use crate::{
+    builder::RoutingConfigBuilder,
+    request::RequestMetadata,
+    routing::{AccessLevel, FunctionRoute, RouteFuture, RoutingConfig},
+    schema::RequestSchema,
+};
+
+// Route registry.
+// --------------------------------------------------
+//
+// Functions annotated with #[route(...)] are registered here at link time, so
+// the routing config does not need to be assembled by hand:
+//
+//   #[route(post, "orders/create", access = User)]
+//   async fn create_order(order: CreateOrder) -> Result<Order, ServerError> {
+//       ...
+//   }
+//
+//   aws_lambda_from_routing_config!(RoutingConfig::from_registered_routes().unwrap());
+//
+// As with register_function_route!, the function can return any serializable
+// data, or an ApiResponse / FileResponse for more control over the response
+// (see IntoApiResponse).
+
+pub struct RouteRegistration {
+    pub path: &'static str,
+    pub access_level: AccessLevel,
+    pub handler: fn(LambdaEvent<ApiGatewayProxyRequest>, RequestMetadata) -> RouteFuture,
+    pub timeout: Option<Duration>,
+    // Schema of the request data type (see schema.rs).
+    pub request_schema: fn() -> RequestSchema,
+}
+
+inventory::collect!(RouteRegistration);
+
+impl RoutingConfigBuilder {
+    // Adds all function routes declared with #[route(...)] in the binary.
+    pub fn registered_routes(self) -> Self {
+        inventory::iter::<RouteRegistration>
+            .into_iter()
+            .fold(self, |builder, registration| {
+                builder.function(
+                    registration.path,
+                    FunctionRoute {
+                        timeout: registration.timeout,
+                        request_schema: (registration.request_schema)(),
+                        ..FunctionRoute::new(
+                            registration.access_level,
+                            Box::new(registration.handler),
+                        )
+                    },
+                )
+            })
+    }
+}
+
+impl RoutingConfig {
+    // Builds a config containing only the routes declared with #[route(...)].
+    pub fn from_registered_routes() -> Result<Self, ServerError> {
+        RoutingConfig::builder().registered_routes().build()
+    }
+}
+
+// Tests.
+// --------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        correlation::CorrelationIds,
+        errors::InvalidRequestError,
+        response::FileResponse,
+        route,
+        testing::{TestClient, TestRequest},
+        Validate, Validator,
+    };
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    #[derive(Deserialize)]
+    struct Greeting {
+        name: String,
+    }
+
+    impl Validate for Greeting {
+        fn validate(&self, v: &mut Validator) {
+            v.length("name", &self.name, 1..=10);
+        }
+    }
+
+    #[derive(Deserialize)]
+    struct GreetingQuery {
+        punctuation: Option<String>,
+    }
+
+    fn reject_admins(_: &Greeting, metadata: RequestMetadata) -> Result<(), ServerError> {
+        match metadata.is_admin {
+            true => Err(InvalidRequestError::new("admins cannot be greeted")),
+            false => Ok(()),
+        }
+    }
+
+    #[route(post, "test/greet", access = Guest, validator = reject_admins, timeout_ms = 1000)]
+    async fn greet(
+        greeting: Greeting,
+        #[query] query: GreetingQuery,
+    ) -> Result<String, ServerError> {
+        Ok(format!(
+            "Hello, {}{}",
+            greeting.name,
+            query.punctuation.as_deref().unwrap_or(".")
+        ))
+    }
+
+    #[route(post, "test/ping")]
+    async fn ping() -> Result<String, ServerError> {
+        Ok("pong".to_string())
+    }
+
+    #[route(post, "test/export", access = Guest)]
+    async fn export() -> Result<FileResponse, ServerError> {
+        Ok(FileResponse::new(vec![0x89u8, b'P', b'N', b'G'], "image/png").as_inline("logo.png"))
+    }
+
+    fn create_event(body: &str, query: &[(&str, &str)]) -> LambdaEvent<ApiGatewayProxyRequest> {
+        let payload = ApiGatewayProxyRequest {
+            body: Some(body.to_string()),
+            query_string_parameters: query
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<HashMap<_, _>>()
+                .into(),
+            ..Default::default()
+        };
+        LambdaEvent::new(payload, Default::default())
+    }
+
+    fn create_metadata() -> RequestMetadata {
+        RequestMetadata {
+            is_authenticated: false,
+            is_admin: false,
+            user_sub: None,
+            correlation_ids: CorrelationIds::from_request(&ApiGatewayProxyRequest::default()),
+            deadline: None,
+        }
+    }
+
+    #[test]
+    fn test_registered_routes_collected() {
+        let config = RoutingConfig::from_registered_routes().unwrap();
+        let greet = config.function_routes().get("test/greet").unwrap();
+        assert_eq!(greet.access_level, AccessLevel::Guest);
+        assert_eq!(greet.timeout, Some(Duration::from_millis(1000)));
+        let ping = config.function_routes().get("test/ping").unwrap();
+        assert_eq!(ping.access_level, AccessLevel::User);
+    }
+
+    #[tokio::test]
+    async fn test_registered_handler_parses_request() {
+        let config = RoutingConfig::from_registered_routes().unwrap();
+        let handler = &config.function_routes().get("test/greet").unwrap().handler;
+
+        let event = create_event("{\"name\":\"Jane\"}", &[("punctuation", "!")]);
+        let response = handler(event, create_metadata()).await.unwrap();
+        assert!(format!("{:?}", response.body).contains("Hello, Jane!"));
+
+        // Declarative rules of the request data are applied.
+        let event = create_event("{\"name\":\"\"}", &[]);
+        let response = handler(event, create_metadata()).await.unwrap();
+        assert!(format!("{:?}", response.body).contains("length must be"));
+
+        // The validator receives the request data and metadata.
+        let event = create_event("{\"name\":\"Jane\"}", &[]);
+        let metadata = RequestMetadata {
+            is_admin: true,
+            ..create_metadata()
+        };
+        let response = handler(event, metadata).await.unwrap();
+        assert!(format!("{:?}", response.body).contains("\\\"ok\\\":false"));
+    }
+
+    #[tokio::test]
+    async fn test_registered_handler_returns_file() {
+        let client = TestClient::new(RoutingConfig::from_registered_routes().unwrap());
+        let response = client.send(TestRequest::post("test/export")).await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.header("content-type"), Some("image/png"));
+        assert_eq!(response.bytes(), vec![0x89u8, b'P', b'N', b'G']);
+    }
+}
@@ -1,9 +1,18 @@
-use aws_lambda_events::apigw::ApiGatewayProxyRequest;
+use std::{
+    borrow::Cow,
+    marker::PhantomData,
+    time::{Duration, SystemTime},
+};
+
+use aws_lambda_events::{apigw::ApiGatewayProxyRequest, http::header::CONTENT_TYPE};
 use fractic_server_error::ServerError;
 
 use crate::{
     auth::{get_sub_of_authenticated_user, is_admin, is_authenticated},
+    body::request_body_bytes,
+    correlation::CorrelationIds,
     errors::InvalidRequestError,
+    form::{parse_form_urlencoded, parse_multipart_form, MultipartForm},
 };
 
 #[derive(Debug, Clone)]
@@ -11,22 +20,104 @@ pub struct RequestMetadata {
     pub is_authenticated: bool,
     pub is_admin: bool,
     pub user_sub: Option<String>,
+    pub correlation_ids: CorrelationIds,
+    // Invocation deadline from the Lambda context (set by handle_route).
+    pub deadline: Option<SystemTime>,
+}
+
+impl RequestMetadata {
+    // Time left before the lambda times out, if the deadline is known.
+    pub fn remaining_time(&self) -> Option<Duration> {
+        self.deadline.map(|deadline| {
+            deadline
+                .duration_since(SystemTime::now())
+                .unwrap_or_default()
+        })
+    }
 }
 
 // API Gateway request utils.
 // --------------------------------------------------
 
+// Parses the request body based on its Content-Type. JSON is assumed if no
+// Content-Type is given. Multipart bodies are rejected, since they can only be
+// parsed into a MultipartForm (see parse_multipart_form).
 pub fn parse_request_data<T>(request: &ApiGatewayProxyRequest) -> Result<T, ServerError>
+where
+    T: serde::de::DeserializeOwned,
+{
+    match media_type(request).as_deref() {
+        Some("application/x-www-form-urlencoded") => parse_form_urlencoded(request),
+        Some(MULTIPART_FORM_DATA) => Err(multipart_not_supported()),
+        _ => parse_json_request_data(request),
+    }
+}
+
+// Used by the registration macros to parse MultipartForm request data with
+// parse_multipart_form, and any other type with parse_request_data (through
+// autoref-based specialization, as for ValidationWrap).
+#[doc(hidden)]
+pub struct RequestDataWrap<T>(pub PhantomData<T>);
+
+#[doc(hidden)]
+pub trait ParseIfMultipartForm {
+    type Output;
+    fn parse(&self, request: &ApiGatewayProxyRequest) -> Result<Self::Output, ServerError>;
+}
+
+impl ParseIfMultipartForm for RequestDataWrap<MultipartForm> {
+    type Output = MultipartForm;
+    fn parse(&self, request: &ApiGatewayProxyRequest) -> Result<MultipartForm, ServerError> {
+        parse_multipart_form(request)
+    }
+}
+
+#[doc(hidden)]
+pub trait ParseFallback {
+    type Output;
+    fn parse(&self, request: &ApiGatewayProxyRequest) -> Result<Self::Output, ServerError>;
+}
+
+impl<T: serde::de::DeserializeOwned> ParseFallback for &RequestDataWrap<T> {
+    type Output = T;
+    fn parse(&self, request: &ApiGatewayProxyRequest) -> Result<T, ServerError> {
+        parse_request_data(request)
+    }
+}
+
+const MULTIPART_FORM_DATA: &str = "multipart/form-data";
+
+fn media_type(request: &ApiGatewayProxyRequest) -> Option<String> {
+    request
+        .headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(';').next())
+        .map(|v| v.trim().to_lowercase())
+}
+
+fn multipart_not_supported() -> ServerError {
+    InvalidRequestError::new("multipart bodies are only accepted by routes taking a MultipartForm")
+}
+
+fn parse_json_request_data<T>(request: &ApiGatewayProxyRequest) -> Result<T, ServerError>
 where
     T: serde::de::DeserializeOwned,
 {
     let body = match &request.body {
-        Some(b) => b,
+        Some(_) if request.is_base64_encoded => {
+            let bytes = request_body_bytes(request)?;
+            Cow::Owned(
+                String::from_utf8(bytes)
+                    .map_err(|e| InvalidRequestError::with_debug("body is not valid UTF-8", &e))?,
+            )
+        }
+        Some(b) => Cow::Borrowed(b.as_str()),
         None => {
             return Err(InvalidRequestError::new("missing request body"));
         }
     };
-    serde_json::from_str(body).map_err(|e| InvalidRequestError::with_debug("parsing error", &e))
+    serde_json::from_str(&body).map_err(|e| InvalidRequestError::with_debug("parsing error", &e))
 }
 
 pub fn parse_request_metadata(
@@ -41,6 +132,8 @@ pub fn parse_request_metadata(
         } else {
             None
         },
+        correlation_ids: CorrelationIds::from_request(request),
+        deadline: None,
     })
 }
 
@@ -102,4 +195,21 @@ mod tests {
         let result = parse_request_data::<TestData>(&request);
         assert!(format!("{:?}", result.unwrap_err()).contains("InvalidRequestError"));
     }
+
+    #[test]
+    fn test_parse_request_base64_body() {
+        let request = ApiGatewayProxyRequest {
+            // Base64 of {"key":"value"}.
+            body: Some("eyJrZXkiOiJ2YWx1ZSJ9".to_string()),
+            is_base64_encoded: true,
+            ..Default::default()
+        };
+        let result = parse_request_data::<TestData>(&request);
+        assert_eq!(
+            result.unwrap(),
+            TestData {
+                key: "value".to_string()
+            }
+        );
+    }
 }
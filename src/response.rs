@@ -5,58 +5,337 @@ use aws_lambda_events::{
         header::{
             ACCESS_CONTROL_ALLOW_CREDENTIALS, ACCESS_CONTROL_ALLOW_HEADERS,
             ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_ALLOW_ORIGIN,
+            ACCESS_CONTROL_EXPOSE_HEADERS, CACHE_CONTROL, CONTENT_DISPOSITION, CONTENT_TYPE, ETAG,
+            LAST_MODIFIED, LOCATION, SET_COOKIE,
         },
-        HeaderMap,
+        HeaderMap, HeaderName, HeaderValue,
     },
 };
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use fractic_server_error::ServerError;
 use lambda_runtime::Error;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::time::SystemTime;
 
-use crate::constants::{INTERNAL_SERVER_ERROR_MSG, UNAUTHORIZED_ERROR_MSG};
+use crate::{
+    caching::http_date,
+    constants::{
+        CORS_ALLOW_CREDENTIALS, CORS_ALLOW_HEADERS, CORS_ALLOW_METHODS, CORS_ALLOW_ORIGIN,
+        CORS_EXPOSE_HEADERS, INTERNAL_SERVER_ERROR_MSG, UNAUTHORIZED_ERROR_MSG,
+    },
+    cookie::Cookie,
+    correlation::{current_correlation_ids, CORRELATION_ID_HEADER, REQUEST_ID_HEADER},
+    errors::InvalidResponseError,
+    fields::current_field_selection,
+    metrics::{record_metrics, MetricUnit},
+};
 
 // API Gateway response utils.
 // --------------------------------------------------
 
 // All API responses are wrapped in the following wrapper:
-#[derive(Debug, Serialize)]
-struct ResponseWrapper<T> {
-    ok: bool,
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct ResponseWrapper<T> {
+    pub(crate) ok: bool,
     // If OK, response data.
-    data: Option<T>,
+    pub(crate) data: Option<T>,
     // If not OK, error message safe to show to user.
-    error: Option<String>,
+    pub(crate) error: Option<String>,
+    // If not OK, ID of the request that can be included in bug reports.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) request_id: Option<String>,
 }
 
 pub fn build_simple(data: impl Into<Body>) -> ApiGatewayProxyResponse {
+    let (body, is_base64_encoded) = match data.into() {
+        Body::Binary(bytes) => (Body::Text(BASE64.encode(bytes)), true),
+        body => (body, false),
+    };
     ApiGatewayProxyResponse {
         status_code: 200,
         headers: build_headers(),
         multi_value_headers: Default::default(),
-        body: Some(data.into()),
-        is_base64_encoded: false,
+        body: Some(body),
+        is_base64_encoded,
     }
 }
 
+// Returns the data as-is (not wrapped in a ResponseWrapper), with the given
+// Content-Type. Binary data is base64-encoded.
+//
+// NOTE: API Gateway only decodes base64 response bodies if the API has binary
+// media types configured (see SamTemplate::with_binary_media_types).
+pub fn build_binary(
+    data: impl Into<Vec<u8>>,
+    content_type: &str,
+) -> Result<ApiGatewayProxyResponse, Error> {
+    build_file(FileResponse::new(data, content_type))
+}
+
+pub fn build_file(file: FileResponse) -> Result<ApiGatewayProxyResponse, Error> {
+    let mut headers = build_headers();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_str(&file.content_type)?);
+    if let Some((disposition, filename)) = &file.disposition {
+        headers.insert(
+            CONTENT_DISPOSITION,
+            HeaderValue::from_str(&content_disposition(disposition, filename))?,
+        );
+    }
+    // Textual files are sent as-is where possible, so that they are returned
+    // correctly even if the API has no binary media types configured.
+    let (body, is_base64_encoded) = match is_text_content_type(&file.content_type) {
+        true => match String::from_utf8(file.content) {
+            Ok(text) => (text, false),
+            Err(e) => (BASE64.encode(e.into_bytes()), true),
+        },
+        false => (BASE64.encode(file.content), true),
+    };
+    Ok(ApiGatewayProxyResponse {
+        status_code: 200,
+        headers,
+        multi_value_headers: Default::default(),
+        body: Some(Body::Text(body)),
+        is_base64_encoded,
+    })
+}
+
+// If the client selected specific fields (see fields.rs), the data is
+// projected down to those fields.
 pub fn build_result<T>(data: T) -> Result<ApiGatewayProxyResponse, Error>
 where
     T: serde::Serialize,
 {
-    let payload = ResponseWrapper {
-        ok: true,
-        data: Some(data),
-        error: None,
+    let body = match current_field_selection() {
+        Some(selection) => serde_json::to_string(&ResponseWrapper {
+            ok: true,
+            data: Some(selection.project(serde_json::to_value(data)?)),
+            error: None,
+            request_id: None,
+        })?,
+        None => serde_json::to_string(&ResponseWrapper {
+            ok: true,
+            data: Some(data),
+            error: None,
+            request_id: None,
+        })?,
     };
     let resp = ApiGatewayProxyResponse {
         status_code: 200,
         headers: build_headers(),
         multi_value_headers: Default::default(),
-        body: Some(serde_json::to_string(&payload)?.into()),
+        body: Some(body.into()),
         is_base64_encoded: false,
     };
     Ok(resp)
 }
 
+// File responses.
+// --------------------------------------------------
+
+// Raw (non-JSON) response data, such as a PDF, image or CSV export. Function
+// routes can return it in place of regular response data, for example:
+//
+//   async fn export_orders() -> Result<FileResponse, ServerError> {
+//       Ok(FileResponse::new(csv, "text/csv").as_attachment("orders.csv"))
+//   }
+#[derive(Debug, Clone)]
+pub struct FileResponse {
+    pub content: Vec<u8>,
+    pub content_type: String,
+    // Content-Disposition type ('attachment' or 'inline') and filename.
+    pub disposition: Option<(String, String)>,
+}
+
+impl FileResponse {
+    pub fn new(content: impl Into<Vec<u8>>, content_type: &str) -> Self {
+        Self {
+            content: content.into(),
+            content_type: content_type.to_string(),
+            disposition: None,
+        }
+    }
+
+    // Prompts browsers to download the file rather than display it.
+    pub fn as_attachment(mut self, filename: &str) -> Self {
+        self.disposition = Some(("attachment".to_string(), filename.to_string()));
+        self
+    }
+
+    pub fn as_inline(mut self, filename: &str) -> Self {
+        self.disposition = Some(("inline".to_string(), filename.to_string()));
+        self
+    }
+}
+
+// Custom responses.
+// --------------------------------------------------
+
+// Response data with a custom status code, headers or cookies. The data is
+// still wrapped as in build_result, for example:
+//
+//   async fn create_order(order: NewOrder) -> Result<ApiResponse<Order>, ServerError> {
+//       let order = db.create(order).await?;
+//       Ok(ApiResponse::created(order).with_location(&format!("/orders/{}", order.id)))
+//   }
+//
+// NOTE: Errors are unaffected, and are still returned as described in
+// build_error.
+#[derive(Debug, Clone)]
+pub struct ApiResponse<T> {
+    pub status_code: i64,
+    // If None, the response has no body (for example 204 No Content).
+    pub data: Option<T>,
+    // Added to (or overriding) the default headers set by build_headers.
+    pub headers: Vec<(String, String)>,
+    pub cookies: Vec<Cookie>,
+}
+
+impl<T> ApiResponse<T> {
+    pub fn ok(data: T) -> Self {
+        Self::with_data(200, data)
+    }
+
+    pub fn created(data: T) -> Self {
+        Self::with_data(201, data)
+    }
+
+    pub fn accepted(data: T) -> Self {
+        Self::with_data(202, data)
+    }
+
+    fn with_data(status_code: i64, data: T) -> Self {
+        Self {
+            status_code,
+            data: Some(data),
+            headers: Vec::new(),
+            cookies: Vec::new(),
+        }
+    }
+
+    pub fn with_status(mut self, status_code: i64) -> Self {
+        self.status_code = status_code;
+        self
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn with_location(self, location: &str) -> Self {
+        self.with_header(LOCATION.as_str(), location)
+    }
+
+    pub fn with_cookie(mut self, cookie: Cookie) -> Self {
+        self.cookies.push(cookie);
+        self
+    }
+
+    // Overrides the ETag computed from the response body (see caching.rs),
+    // for example with the version of the returned item. Quoted if needed.
+    pub fn with_etag(self, etag: &str) -> Self {
+        let etag = match etag.ends_with('"') {
+            true => etag.to_string(),
+            false => format!("\"{}\"", etag),
+        };
+        self.with_header(ETAG.as_str(), &etag)
+    }
+
+    // Enables If-Modified-Since requests to be answered with 304 Not Modified.
+    pub fn with_last_modified(self, last_modified: SystemTime) -> Self {
+        self.with_header(LAST_MODIFIED.as_str(), &http_date(last_modified))
+    }
+
+    pub fn with_cache_control(self, cache_control: &str) -> Self {
+        self.with_header(CACHE_CONTROL.as_str(), cache_control)
+    }
+}
+
+impl ApiResponse<()> {
+    pub fn no_content() -> Self {
+        Self {
+            status_code: 204,
+            data: None,
+            headers: Vec::new(),
+            cookies: Vec::new(),
+        }
+    }
+}
+
+// Conversion of a route's result into the API response, used by the route
+// registration macros. Any serializable data is wrapped as in build_result,
+// while a FileResponse is returned as-is (see build_file).
+pub trait IntoApiResponse {
+    fn into_api_response(self) -> Result<ApiGatewayProxyResponse, Error>;
+}
+
+impl<T: Serialize> IntoApiResponse for T {
+    fn into_api_response(self) -> Result<ApiGatewayProxyResponse, Error> {
+        build_result(self)
+    }
+}
+
+impl IntoApiResponse for FileResponse {
+    fn into_api_response(self) -> Result<ApiGatewayProxyResponse, Error> {
+        build_file(self)
+    }
+}
+
+impl<T: Serialize> IntoApiResponse for ApiResponse<T> {
+    fn into_api_response(self) -> Result<ApiGatewayProxyResponse, Error> {
+        let mut response = match self.data {
+            Some(data) => build_result(data)?,
+            None => ApiGatewayProxyResponse {
+                status_code: 200,
+                headers: build_headers(),
+                multi_value_headers: Default::default(),
+                body: None,
+                is_base64_encoded: false,
+            },
+        };
+
+        // An invalid response is a bug in the route, so is returned as an
+        // internal error rather than partially applied.
+        let mut problems = Vec::new();
+        if !(100..=599).contains(&self.status_code) {
+            problems.push(format!("invalid status code {}", self.status_code));
+        }
+        for (name, value) in &self.headers {
+            match (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(value),
+            ) {
+                (Ok(name), Ok(value)) => {
+                    response.headers.insert(name, value);
+                }
+                _ => problems.push(format!("invalid header '{}'", name)),
+            }
+        }
+        // Sent as multi-value headers, since a single Set-Cookie header can
+        // only hold one cookie.
+        for cookie in &self.cookies {
+            match cookie
+                .to_header_value()
+                .and_then(|v| HeaderValue::from_str(&v).map_err(|e| e.to_string()))
+            {
+                Ok(value) => {
+                    response.multi_value_headers.append(SET_COOKIE, value);
+                }
+                Err(problem) => problems.push(problem),
+            }
+        }
+        if !problems.is_empty() {
+            return build_error(InvalidResponseError::new(&problems.join("; ")));
+        }
+
+        response.status_code = self.status_code;
+        Ok(response)
+    }
+}
+
+// Error responses.
+// --------------------------------------------------
+
 pub fn build_error(error: ServerError) -> Result<ApiGatewayProxyResponse, Error> {
     enum LoggingLevel {
         Error,
@@ -64,30 +343,64 @@ pub fn build_error(error: ServerError) -> Result<ApiGatewayProxyResponse, Error>
         Info,
     }
 
+    let request_id =
+        current_correlation_ids().and_then(|ids| ids.request_id().map(|id| id.to_string()));
+
+    // Count errors by behaviour in the metrics record of the current request.
+    let behaviour_name = match error.behaviour() {
+        fractic_server_error::ServerErrorBehaviour::ForwardToClient => "ForwardToClient",
+        fractic_server_error::ServerErrorBehaviour::LogWarningForwardToClient => {
+            "LogWarningForwardToClient"
+        }
+        fractic_server_error::ServerErrorBehaviour::LogErrorForwardToClient => {
+            "LogErrorForwardToClient"
+        }
+        fractic_server_error::ServerErrorBehaviour::LogWarningSendFixedMsgToClient(_) => {
+            "LogWarningSendFixedMsgToClient"
+        }
+        fractic_server_error::ServerErrorBehaviour::LogErrorSendFixedMsgToClient(_) => {
+            "LogErrorSendFixedMsgToClient"
+        }
+        fractic_server_error::ServerErrorBehaviour::ReturnInternalServerError => {
+            "ReturnInternalServerError"
+        }
+        fractic_server_error::ServerErrorBehaviour::ReturnUnauthorized => "ReturnUnauthorized",
+    };
+    record_metrics(|m| {
+        m.put_metric("Errors", 1.0, MetricUnit::Count);
+        m.put_metric(
+            &format!("Errors.{}", behaviour_name),
+            1.0,
+            MetricUnit::Count,
+        );
+        m.set_property("ErrorBehaviour", behaviour_name.into());
+    });
+
     // Two ways to handle errors:
 
     // 1) Forward to the client by wrapping the error in a 200 response. This
     // allows the client to gracefully handle it.
     let forward_to_client = |public_msg: &str, logging_level: LoggingLevel| {
         match logging_level {
-            LoggingLevel::Error => eprintln!("ERROR\n{}", error),
-            LoggingLevel::Warning => println!("WARNING\n{}", error),
-            LoggingLevel::Info => println!("INFO\n{}", error),
+            LoggingLevel::Error => tracing::error!("{}", error),
+            LoggingLevel::Warning => tracing::warn!("{}", error),
+            LoggingLevel::Info => tracing::info!("{}", error),
         }
-        println!("NOTE: Forwarding error to client. Returning 200 response.");
+        tracing::info!("NOTE: Forwarding error to client. Returning 200 response.");
         // Since the data field will be set to None, we need to specify the
         // correct type T, so just use int.
         let payload = ResponseWrapper::<i8> {
             ok: false,
             data: None,
             error: Some(public_msg.into()),
+            request_id: request_id.clone(),
         };
         Ok::<_, Error>(ApiGatewayProxyResponse {
             // Outer status code should still be 200 for client-errors,
             // otherwise Amplify will treat it as a server error. The client
             // will know there is a client error because ok == false.
             status_code: 200,
-            headers: build_headers(),
+            headers: build_error_headers(),
             multi_value_headers: Default::default(),
             body: Some(serde_json::to_string(&payload)?.into()),
             is_base64_encoded: false,
@@ -97,12 +410,16 @@ pub fn build_error(error: ServerError) -> Result<ApiGatewayProxyResponse, Error>
     // 2) Return an error response, triggerring alerting, affecting lambda
     // statistics, and avoiding leaking any error data to the client.
     let error_response = |error_code: i64, public_msg: &str| {
-        eprintln!("ERROR\n{}", error);
+        tracing::error!("{}", error);
+        let body = match &request_id {
+            Some(id) => format!("{}\n\nRequest ID: {}", public_msg, id),
+            None => public_msg.to_string(),
+        };
         Ok::<_, Error>(ApiGatewayProxyResponse {
             status_code: error_code,
-            headers: build_headers(),
+            headers: build_error_headers(),
             multi_value_headers: Default::default(),
-            body: Some(public_msg.into()),
+            body: Some(body.into()),
             is_base64_encoded: false,
         })
     };
@@ -133,6 +450,20 @@ pub fn build_error(error: ServerError) -> Result<ApiGatewayProxyResponse, Error>
     }
 }
 
+// Same as build_error, but overrides the status code of the response. By
+// default, errors forwarded to the client use a 200 status code (see
+// build_error), so this should only be used where an HTTP-level status is
+// explicitly required (for example, 415 Unsupported Media Type).
+pub fn build_error_with_status(
+    error: ServerError,
+    status_code: i64,
+) -> Result<ApiGatewayProxyResponse, Error> {
+    build_error(error).map(|mut response| {
+        response.status_code = status_code;
+        response
+    })
+}
+
 // Helper functions.
 // --------------------------------------------------
 
@@ -151,33 +482,96 @@ fn build_headers() -> HeaderMap {
     // handled separately, and should also respond with the same CORS response
     // headers as we do here (and no body). Those preflight handlers can be
     // auto-generated by API Gateway by configuring the 'Cors' property on the
-    // AWS::Serverless::Api resource:
-    //
-    //   Cors:
-    //     AllowMethods: "'GET, POST, PUT, DELETE'"
-    //     AllowHeaders: "'Content-Type,X-Amz-Date,Authorization,X-Api-Key,X-Amz-Security-Token,X-Amz-User-Agent'"
-    //     AllowOrigin: "'https://example.com'"
-    //     MaxAge: "'600'"
-    //     AllowCredentials: true
-    //   Auth:
-    //     AddApiKeyRequiredToCorsPreflight: false
-    //     AddDefaultAuthorizerToCorsPreflight: false
+    // AWS::Serverless::Api resource, which SamTemplate generates from the same
+    // constants.
     //
     headers.insert(
         ACCESS_CONTROL_ALLOW_ORIGIN,
-        "https://fractic.io".parse().unwrap(),
+        CORS_ALLOW_ORIGIN.parse().unwrap(),
     );
     headers.insert(
         ACCESS_CONTROL_ALLOW_HEADERS,
-        "Content-Type,X-Amz-Date,Authorization,X-Api-Key,X-Amz-Security-Token,X-Amz-User-Agent"
-            .parse()
-            .unwrap(),
+        CORS_ALLOW_HEADERS.parse().unwrap(),
     );
     headers.insert(
         ACCESS_CONTROL_ALLOW_METHODS,
-        "GET, POST, PUT, DELETE".parse().unwrap(),
+        CORS_ALLOW_METHODS.parse().unwrap(),
     );
-    headers.insert(ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".parse().unwrap());
+    headers.insert(
+        ACCESS_CONTROL_ALLOW_CREDENTIALS,
+        CORS_ALLOW_CREDENTIALS.to_string().parse().unwrap(),
+    );
+    headers.insert(
+        ACCESS_CONTROL_EXPOSE_HEADERS,
+        CORS_EXPOSE_HEADERS.parse().unwrap(),
+    );
+    //
+    // Echo back the IDs of the request being handled, so that client-side
+    // reports can be matched against the server logs.
+    //
+    if let Some(ids) = current_correlation_ids() {
+        if let Some(id) = ids.request_id().and_then(|id| id.parse().ok()) {
+            headers.insert(REQUEST_ID_HEADER, id);
+        }
+        if let Some(id) = ids.client_request_id.and_then(|id| id.parse().ok()) {
+            headers.insert(CORRELATION_ID_HEADER, id);
+        }
+    }
+    headers
+}
+
+fn is_text_content_type(content_type: &str) -> bool {
+    let essence = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    essence.starts_with("text/")
+        || essence.ends_with("+json")
+        || essence.ends_with("+xml")
+        || matches!(
+            essence.as_str(),
+            "application/json" | "application/xml" | "application/javascript"
+        )
+}
+
+// Builds the Content-Disposition header value. Non-ASCII filenames are
+// included using the RFC 6266 'filename*' parameter, with an ASCII fallback
+// for older clients.
+fn content_disposition(disposition: &str, filename: &str) -> String {
+    let fallback: String = filename
+        .chars()
+        .map(|c| match c {
+            '"' | '\\' => '_',
+            c if c.is_ascii() && !c.is_ascii_control() => c,
+            _ => '_',
+        })
+        .collect();
+    if fallback == filename {
+        return format!("{}; filename=\"{}\"", disposition, filename);
+    }
+    let encoded: String = filename
+        .bytes()
+        .map(|b| match b {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' => (b as char).to_string(),
+            b'!' | b'#' | b'$' | b'&' | b'+' | b'-' | b'.' | b'^' | b'_' | b'`' | b'|' | b'~' => {
+                (b as char).to_string()
+            }
+            b => format!("%{:02X}", b),
+        })
+        .collect();
+    format!(
+        "{}; filename=\"{}\"; filename*=UTF-8''{}",
+        disposition, fallback, encoded
+    )
+}
+
+// Errors are specific to the request (and may be transient), so should never
+// be cached by clients or proxies.
+fn build_error_headers() -> HeaderMap {
+    let mut headers = build_headers();
+    headers.insert(CACHE_CONTROL, HeaderValue::from_static("no-store"));
     headers
 }
 
@@ -186,7 +580,10 @@ fn build_headers() -> HeaderMap {
 
 #[cfg(test)]
 mod tests {
-    use crate::UnauthorizedError;
+    use crate::{
+        correlation::{with_correlation_ids, CorrelationIds},
+        UnauthorizedError,
+    };
 
     use super::*;
     use aws_lambda_events::encodings::Body;
@@ -232,6 +629,89 @@ mod tests {
         assert_eq!(body["error"].is_null(), true);
     }
 
+    #[test]
+    fn test_build_simple_binary() {
+        let result = build_simple(vec![0xffu8, 0x00]);
+        assert!(result.is_base64_encoded);
+        assert!(matches!(result.body, Some(Body::Text(b)) if b == "/wA="));
+
+        let result = build_simple("plain text");
+        assert!(!result.is_base64_encoded);
+    }
+
+    #[test]
+    fn test_build_file() {
+        let pdf = FileResponse::new(vec![0x25u8, 0x50, 0x44, 0x46, 0xff], "application/pdf")
+            .as_attachment("report.pdf");
+        let result = pdf.into_api_response().unwrap();
+        assert!(result.is_base64_encoded);
+        assert_eq!(result.headers[CONTENT_TYPE], "application/pdf");
+        assert_eq!(
+            result.headers[CONTENT_DISPOSITION],
+            "attachment; filename=\"report.pdf\""
+        );
+        assert!(result.headers.contains_key(ACCESS_CONTROL_ALLOW_ORIGIN));
+
+        let csv = FileResponse::new("id,name\n1,Café\n", "text/csv; charset=utf-8")
+            .as_attachment("café \"export\".csv");
+        let result = build_file(csv).unwrap();
+        assert!(!result.is_base64_encoded);
+        assert!(matches!(result.body, Some(Body::Text(b)) if b == "id,name\n1,Café\n"));
+        assert_eq!(
+            result.headers[CONTENT_DISPOSITION],
+            "attachment; filename=\"caf_ _export_.csv\"; \
+             filename*=UTF-8''caf%C3%A9%20%22export%22.csv"
+        );
+
+        assert!(build_binary(vec![0u8], "invalid\ntype").is_err());
+    }
+
+    #[test]
+    fn test_api_response() {
+        let result = ApiResponse::created(MockResponseData {
+            key: "Test value.".to_string(),
+        })
+        .with_location("/items/1")
+        .with_header("X-Custom", "value")
+        .with_cookie(Cookie::new("a", "1"))
+        .with_cookie(Cookie::removal("b"))
+        .into_api_response()
+        .unwrap();
+        assert_eq!(result.status_code, 201);
+        assert_eq!(result.headers[LOCATION], "/items/1");
+        assert_eq!(result.headers["x-custom"], "value");
+        assert!(result.headers.contains_key(ACCESS_CONTROL_ALLOW_ORIGIN));
+        assert_eq!(
+            result
+                .multi_value_headers
+                .get_all(SET_COOKIE)
+                .iter()
+                .count(),
+            2
+        );
+        let body: Value = serde_json::from_str(match &result.body.unwrap() {
+            Body::Text(b) => b,
+            _ => panic!("Expected response body."),
+        })
+        .unwrap();
+        assert_eq!(body["data"]["key"].as_str().unwrap(), "Test value.");
+
+        let result = ApiResponse::no_content().into_api_response().unwrap();
+        assert_eq!(result.status_code, 204);
+        assert!(result.body.is_none());
+    }
+
+    #[test]
+    fn test_invalid_api_response() {
+        let result = ApiResponse::ok("data")
+            .with_status(1000)
+            .with_header("Bad Header", "value")
+            .into_api_response()
+            .unwrap();
+        assert_eq!(result.status_code, 500);
+        assert!(result.headers.get("bad header").is_none());
+    }
+
     #[test]
     fn test_build_user_error() {
         define_user_error!(TestError, "User error: {details}.", { details: &str });
@@ -287,6 +767,7 @@ mod tests {
         };
         assert_eq!(result.status_code, 500);
         assert!(!body.contains("internal error message"));
+        assert_eq!(result.headers[CACHE_CONTROL], "no-store");
     }
 
     #[test]
@@ -301,4 +782,24 @@ mod tests {
         assert_eq!(result.status_code, 401);
         assert!(!body.contains("internal authentication error message"));
     }
+
+    #[tokio::test]
+    async fn test_build_error_includes_request_id() {
+        define_user_error!(TestError, "User error: {details}.", { details: &str });
+        let ids = CorrelationIds {
+            api_request_id: Some("apigw-id".into()),
+            ..Default::default()
+        };
+        let result = with_correlation_ids(ids, async { build_error(TestError::new("test")) })
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_str(match &result.body.unwrap() {
+            Body::Text(b) => b,
+            _ => panic!("Expected response body."),
+        })
+        .unwrap();
+
+        assert_eq!(body["request_id"].as_str().unwrap(), "apigw-id");
+        assert_eq!(result.headers.get(REQUEST_ID_HEADER).unwrap(), "apigw-id");
+    }
 }
@@ -2,39 +2,102 @@ use std::collections::HashMap;
 
 use aws_lambda_events::{
     apigw::{ApiGatewayProxyRequest, ApiGatewayProxyResponse},
-    http::Method,
+    http::{
+        header::{ACCEPT_ENCODING, CACHE_CONTROL},
+        Method,
+    },
 };
 use core::future::Future;
+use fractic_server_error::CriticalError;
+use futures::FutureExt;
 use lambda_runtime::{Error, LambdaEvent};
-use std::pin::Pin;
+use std::{
+    any::Any,
+    panic::AssertUnwindSafe,
+    pin::Pin,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tracing::Instrument;
 
 use crate::{
-    errors::{InvalidRouteError, UnauthorizedError},
+    body::{prepare_request_body_with_status, RequestBodyConfig},
+    builder::normalize_path,
+    caching::{apply_conditional_get, CacheConditions},
+    compression::{compress_response, ResponseCompression},
+    correlation::{with_correlation_ids, CorrelationIds},
+    errors::{InvalidRouteError, RequestTimeoutError, UnauthorizedError},
+    fields::{parse_field_selection, with_field_selection, FieldSelection},
+    metrics::{flush_metrics, record_metrics, take_cold_start, with_metrics, MetricUnit},
     request::{parse_request_metadata, RequestMetadata},
+    schema::RequestSchema,
 };
 
-use super::response::build_error;
+use super::response::{build_error, build_error_with_status};
 
 // API Gateway routing config.
 // --------------------------------------------------
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AccessLevel {
     Guest,
     User,
     Admin,
     None,
+    // Takes the access level of the enclosing RouteGroup. Routes which still
+    // inherit after all groups are resolved are rejected by the builder.
+    Inherit,
 }
 
-type RouteHandler = Box<
-    dyn Fn(
-        LambdaEvent<ApiGatewayProxyRequest>,
-        RequestMetadata,
-    ) -> Pin<Box<dyn Future<Output = Result<ApiGatewayProxyResponse, Error>>>>,
->;
+pub type RouteFuture = Pin<Box<dyn Future<Output = Result<ApiGatewayProxyResponse, Error>> + Send>>;
+
+pub type RouteHandler =
+    Box<dyn Fn(LambdaEvent<ApiGatewayProxyRequest>, RequestMetadata) -> RouteFuture + Send + Sync>;
 
 pub struct FunctionRoute {
     pub access_level: AccessLevel,
     pub handler: RouteHandler,
+    // Optional maximum run time of the handler. Regardless of this setting,
+    // handlers are cancelled shortly before the lambda's deadline.
+    pub timeout: Option<Duration>,
+    pub body_config: RequestBodyConfig,
+    // Selection of response fields with the 'fields' query parameter (see
+    // fields.rs). Enabled for all fields by default.
+    pub field_selection: FieldSelection,
+    // Schema of the request data for the OpenAPI document (see schema.rs).
+    pub request_schema: RequestSchema,
+}
+
+impl FunctionRoute {
+    pub fn new(access_level: AccessLevel, handler: RouteHandler) -> Self {
+        FunctionRoute {
+            access_level,
+            handler,
+            timeout: None,
+            body_config: RequestBodyConfig::default(),
+            field_selection: FieldSelection::default(),
+            request_schema: RequestSchema::none(),
+        }
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn with_body_config(mut self, body_config: RequestBodyConfig) -> Self {
+        self.body_config = body_config;
+        self
+    }
+
+    pub fn with_field_selection(mut self, field_selection: FieldSelection) -> Self {
+        self.field_selection = field_selection;
+        self
+    }
+
+    pub fn with_request_schema(mut self, request_schema: RequestSchema) -> Self {
+        self.request_schema = request_schema;
+        self
+    }
 }
 
 pub struct CrudRoute {
@@ -43,13 +106,57 @@ pub struct CrudRoute {
     pub update_access_level: AccessLevel,
     pub delete_access_level: AccessLevel,
     pub handler: RouteHandler,
+    pub timeout: Option<Duration>,
+    pub body_config: RequestBodyConfig,
+    pub field_selection: FieldSelection,
+    // Cache-Control header of successful reads (for example
+    // "private, max-age=60"), unless set by the handler. See caching.rs for
+    // the ETag and conditional request handling.
+    pub cache_control: Option<String>,
+}
+
+// Route matched for an incoming request.
+struct RouteMatch<'a> {
+    path: &'a str,
+    handler: &'a RouteHandler,
+    access_level: &'a AccessLevel,
+    timeout: Option<Duration>,
+    body_config: &'a RequestBodyConfig,
+    field_selection: &'a FieldSelection,
+    cache_control: Option<&'a str>,
 }
 
+// Built with RoutingConfig::builder(), which validates and normalizes the
+// route paths.
 pub struct RoutingConfig {
-    pub function_routes: HashMap<String, FunctionRoute>,
-    pub crud_routes: HashMap<String, CrudRoute>,
+    pub(crate) function_routes: HashMap<String, FunctionRoute>,
+    pub(crate) crud_routes: HashMap<String, CrudRoute>,
+    pub(crate) compression: Option<ResponseCompression>,
+}
+
+impl RoutingConfig {
+    pub fn function_routes(&self) -> &HashMap<String, FunctionRoute> {
+        &self.function_routes
+    }
+
+    pub fn crud_routes(&self) -> &HashMap<String, CrudRoute> {
+        &self.crud_routes
+    }
+
+    pub fn compression(&self) -> Option<&ResponseCompression> {
+        self.compression.as_ref()
+    }
 }
 
+// Route and AccessLevel dimension value of requests which did not match a
+// route.
+const UNMATCHED_ROUTE: &str = "-";
+
+// Time reserved before the lambda's deadline to build and return the timeout
+// error response, so that the client does not receive API Gateway's bare
+// 502/504 response.
+const DEADLINE_SAFETY_MARGIN: Duration = Duration::from_millis(500);
+
 // API Gateway routing utils.
 // --------------------------------------------------
 
@@ -57,23 +164,41 @@ pub fn box_route_handler<T>(
     f: fn(LambdaEvent<ApiGatewayProxyRequest>, RequestMetadata) -> T,
 ) -> RouteHandler
 where
-    T: Future<Output = Result<ApiGatewayProxyResponse, Error>> + 'static,
+    T: Future<Output = Result<ApiGatewayProxyResponse, Error>> + Send + 'static,
 {
     Box::new(move |e, m| Box::pin(f(e, m)))
 }
 
+// Path of the route being requested. This is the {proxy+} path parameter if
+// the API uses a catch-all resource, otherwise the resource path itself (for
+// APIs with one resource per route, as generated by SamTemplate). The resource
+// is used rather than the request path, which also includes the base path when
+// the API is reached through a custom domain.
+fn route_path(request: &ApiGatewayProxyRequest) -> Option<String> {
+    request
+        .path_parameters
+        .get("proxy")
+        .or(request.resource.as_ref())
+        .map(|path| normalize_path(path))
+}
+
 fn find_function_route<'a>(
     config: &'a RoutingConfig,
     event: &LambdaEvent<ApiGatewayProxyRequest>,
-) -> Option<(&'a RouteHandler, &'a AccessLevel)> {
+) -> Option<RouteMatch<'a>> {
     let method = &event.payload.http_method;
     if method == Method::POST {
-        event
-            .payload
-            .path_parameters
-            .get("proxy")
-            .and_then(|proxy| config.function_routes.get(proxy))
-            .map(|route| (&route.handler, &route.access_level))
+        route_path(&event.payload)
+            .and_then(|path| config.function_routes.get_key_value(&path))
+            .map(|(path, route)| RouteMatch {
+                path,
+                handler: &route.handler,
+                access_level: &route.access_level,
+                timeout: route.timeout,
+                body_config: &route.body_config,
+                field_selection: &route.field_selection,
+                cache_control: None,
+            })
     } else {
         None
     }
@@ -82,53 +207,378 @@ fn find_function_route<'a>(
 fn find_crud_route<'a>(
     config: &'a RoutingConfig,
     event: &LambdaEvent<ApiGatewayProxyRequest>,
-) -> Option<(&'a RouteHandler, &'a AccessLevel)> {
+) -> Option<RouteMatch<'a>> {
     let method = &event.payload.http_method;
-    event
-        .payload
-        .path_parameters
-        .get("proxy")
-        .and_then(|proxy| config.crud_routes.get(proxy))
-        .map(|route| {
-            (
-                &route.handler,
-                match method {
-                    &Method::POST => &route.create_access_level,
-                    &Method::GET => &route.read_access_level,
-                    &Method::PUT => &route.update_access_level,
-                    &Method::DELETE => &route.delete_access_level,
-                    _ => &AccessLevel::None,
-                },
-            )
+    route_path(&event.payload)
+        .and_then(|path| config.crud_routes.get_key_value(&path))
+        .map(|(path, route)| RouteMatch {
+            path,
+            handler: &route.handler,
+            access_level: match method {
+                &Method::POST => &route.create_access_level,
+                &Method::GET => &route.read_access_level,
+                &Method::PUT => &route.update_access_level,
+                &Method::DELETE => &route.delete_access_level,
+                _ => &AccessLevel::None,
+            },
+            timeout: route.timeout,
+            body_config: &route.body_config,
+            field_selection: &route.field_selection,
+            cache_control: match method {
+                &Method::GET => route.cache_control.as_deref(),
+                _ => None,
+            },
         })
 }
 
 pub async fn handle_route(
-    config: RoutingConfig,
+    config: &RoutingConfig,
     event: LambdaEvent<ApiGatewayProxyRequest>,
 ) -> Result<ApiGatewayProxyResponse, Error> {
-    let metadata = match parse_request_metadata(&event.payload) {
+    // Make the request's correlation IDs available to all log lines and
+    // response builders for the duration of the request.
+    let correlation_ids = CorrelationIds::from_event(&event);
+    let span = correlation_ids.span();
+    let start = Instant::now();
+    let method = event.payload.http_method.to_string();
+    let accept_encoding = event
+        .payload
+        .headers
+        .get(ACCEPT_ENCODING)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string());
+    let cache_conditions = CacheConditions::from_request(&event.payload);
+    let (mut result, mut record) = with_correlation_ids(
+        correlation_ids.clone(),
+        with_metrics(dispatch_route(config, event, correlation_ids.clone())),
+    )
+    .instrument(span)
+    .await;
+
+    if let (Ok(response), Some(compression)) = (&mut result, &config.compression) {
+        compress_response(response, accept_encoding.as_deref(), compression);
+    }
+    if let Ok(response) = &mut result {
+        apply_conditional_get(response, &cache_conditions);
+    }
+
+    // Emit one metrics record per invocation, regardless of the outcome.
+    record.put_dimension("Method", &method);
+    record.put_dimension(
+        "Status",
+        &match &result {
+            Ok(response) => response.status_code.to_string(),
+            Err(_) => "Error".to_string(),
+        },
+    );
+    record.put_metric(
+        "Latency",
+        start.elapsed().as_secs_f64() * 1000.0,
+        MetricUnit::Milliseconds,
+    );
+    record.put_metric("Requests", 1.0, MetricUnit::Count);
+    if take_cold_start() {
+        record.put_metric("ColdStart", 1.0, MetricUnit::Count);
+    }
+    if let Some(request_id) = correlation_ids.request_id() {
+        record.set_property("RequestId", request_id.into());
+    }
+    flush_metrics(&record);
+
+    result
+}
+
+async fn dispatch_route(
+    config: &RoutingConfig,
+    mut event: LambdaEvent<ApiGatewayProxyRequest>,
+    correlation_ids: CorrelationIds,
+) -> Result<ApiGatewayProxyResponse, Error> {
+    // Dimensions are reported even if the request is rejected before a route
+    // is matched, so that all records share the same dimension set. Unmatched
+    // paths are not reported individually, since every distinct dimension
+    // value becomes a separate (billed) CloudWatch metric.
+    record_metrics(|m| {
+        m.put_dimension("Route", UNMATCHED_ROUTE);
+        m.put_dimension("AccessLevel", UNMATCHED_ROUTE);
+    });
+
+    let mut metadata = match parse_request_metadata(&event.payload) {
         Ok(m) => m,
         Err(e) => return build_error(e),
     };
+    metadata.correlation_ids = correlation_ids;
+    if event.context.deadline > 0 {
+        metadata.deadline = Some(UNIX_EPOCH + Duration::from_millis(event.context.deadline));
+    }
 
+    let route = route_path(&event.payload);
     let route_search =
-        find_function_route(&config, &event).or_else(|| find_crud_route(&config, &event));
-    let (handler, access_level) = match route_search {
-        Some((handler, access_level)) => (handler, access_level),
+        find_function_route(config, &event).or_else(|| find_crud_route(config, &event));
+    let route_match = match route_search {
+        Some(route_match) => route_match,
         None => return build_error(InvalidRouteError::new(event.payload.path)),
     };
+    record_metrics(|m| {
+        m.put_dimension("Route", route_match.path);
+        m.put_dimension("AccessLevel", &format!("{:?}", route_match.access_level));
+    });
 
-    let is_authenticated_for_route = match access_level {
+    let is_authenticated_for_route = match route_match.access_level {
         AccessLevel::Guest => true,
         AccessLevel::User => metadata.is_authenticated,
         AccessLevel::Admin => metadata.is_authenticated && metadata.is_admin,
-        AccessLevel::None => false,
+        AccessLevel::None | AccessLevel::Inherit => false,
     };
 
-    if is_authenticated_for_route {
+    if !is_authenticated_for_route {
+        return build_error(UnauthorizedError::new());
+    }
+
+    if let Err((status_code, error)) =
+        prepare_request_body_with_status(&mut event.payload, route_match.body_config)
+    {
+        return build_error_with_status(error, status_code);
+    }
+
+    let field_selection = match parse_field_selection(&event.payload, route_match.field_selection) {
+        Ok(field_selection) => field_selection,
+        Err(e) => return build_error(e),
+    };
+
+    let handler = route_match.handler;
+    let cache_control = route_match.cache_control;
+    let timeout = effective_timeout(route_match.timeout, metadata.deadline);
+
+    // Catch panics from the handler (including its validators), so that the
+    // client still receives a well-formed response with the crate's headers,
+    // instead of API Gateway's raw 502.
+    let handler_future = AssertUnwindSafe(with_field_selection(field_selection, async {
         handler(event, metadata).await
+    }))
+    .catch_unwind();
+    let handler_outcome = match timeout {
+        Some(timeout) => match tokio::time::timeout(timeout, handler_future).await {
+            Ok(outcome) => outcome,
+            Err(_) => {
+                tracing::warn!(
+                    "Handler for route '{}' cancelled after {}ms.",
+                    route.as_deref().unwrap_or("-"),
+                    timeout.as_millis()
+                );
+                return build_error(RequestTimeoutError::new());
+            }
+        },
+        None => handler_future.await,
+    };
+    match handler_outcome {
+        Ok(Ok(mut response)) => {
+            if let Some(cache_control) = cache_control {
+                // Validated by the builder.
+                if let (200, false, Ok(value)) = (
+                    response.status_code,
+                    response.headers.contains_key(CACHE_CONTROL),
+                    cache_control.parse(),
+                ) {
+                    response.headers.insert(CACHE_CONTROL, value);
+                }
+            }
+            Ok(response)
+        }
+        Ok(Err(e)) => Err(e),
+        Err(panic) => {
+            let route = route.as_deref().unwrap_or("-");
+            let panic_msg = panic_message(&*panic);
+            build_error(CriticalError::new(&format!(
+                "handler for route '{}' panicked: {}",
+                route, panic_msg
+            )))
+        }
+    }
+}
+
+// Helper functions.
+// --------------------------------------------------
+
+// The handler is cancelled at whichever comes first: the route's own timeout,
+// or shortly before the lambda's deadline.
+fn effective_timeout(
+    route_timeout: Option<Duration>,
+    deadline: Option<SystemTime>,
+) -> Option<Duration> {
+    let until_deadline = deadline.map(|deadline| {
+        deadline
+            .duration_since(SystemTime::now())
+            .unwrap_or_default()
+            .saturating_sub(DEADLINE_SAFETY_MARGIN)
+    });
+    match (route_timeout, until_deadline) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    if let Some(msg) = panic.downcast_ref::<&str>() {
+        msg
+    } else if let Some(msg) = panic.downcast_ref::<String>() {
+        msg
     } else {
-        build_error(UnauthorizedError::new())
+        "unknown panic payload"
+    }
+}
+
+// Tests.
+// --------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::ok_route;
+    use lambda_runtime::Context;
+
+    async fn panicking_handler(
+        _: LambdaEvent<ApiGatewayProxyRequest>,
+        _: RequestMetadata,
+    ) -> Result<ApiGatewayProxyResponse, Error> {
+        panic!("test panic");
+    }
+
+    async fn slow_handler(
+        _: LambdaEvent<ApiGatewayProxyRequest>,
+        _: RequestMetadata,
+    ) -> Result<ApiGatewayProxyResponse, Error> {
+        tokio::time::sleep(Duration::from_secs(10)).await;
+        crate::build_result("too late")
+    }
+
+    #[test]
+    fn test_effective_timeout() {
+        let deadline = SystemTime::now() + Duration::from_secs(10);
+        let until_deadline = effective_timeout(None, Some(deadline)).unwrap();
+        assert!(until_deadline <= Duration::from_secs(10) - DEADLINE_SAFETY_MARGIN);
+        assert!(until_deadline > Duration::from_secs(9));
+        assert_eq!(
+            effective_timeout(Some(Duration::from_secs(1)), Some(deadline)),
+            Some(Duration::from_secs(1))
+        );
+        assert_eq!(effective_timeout(None, None), None);
+        assert_eq!(
+            effective_timeout(None, Some(SystemTime::now() - Duration::from_secs(1))),
+            Some(Duration::ZERO)
+        );
+    }
+
+    #[test]
+    fn test_handle_route_future_is_send() {
+        fn assert_send<T: Send>(_: &T) {}
+        let config = RoutingConfig::builder()
+            .function(
+                "slow",
+                FunctionRoute::new(AccessLevel::Guest, box_route_handler(slow_handler)),
+            )
+            .build()
+            .unwrap();
+        let event = LambdaEvent::new(ApiGatewayProxyRequest::default(), Context::default());
+        // Allows serving the shared config from multi-threaded runtimes.
+        assert_send(&handle_route(&config, event));
+    }
+
+    #[tokio::test]
+    async fn test_handler_timeout_returns_error() {
+        let config = RoutingConfig::builder()
+            .function(
+                "slow",
+                FunctionRoute::new(AccessLevel::Guest, box_route_handler(slow_handler))
+                    .with_timeout(Duration::from_millis(10)),
+            )
+            .build()
+            .unwrap();
+        let request = ApiGatewayProxyRequest {
+            http_method: Method::POST,
+            path_parameters: [("proxy".to_string(), "slow".to_string())].into(),
+            ..Default::default()
+        };
+        let response = handle_route(&config, LambdaEvent::new(request, Context::default()))
+            .await
+            .unwrap();
+        let body = match response.body.unwrap() {
+            aws_lambda_events::encodings::Body::Text(b) => b,
+            _ => panic!("Expected response body."),
+        };
+        assert!(body.contains("took too long"));
+        assert!(response.headers.contains_key("access-control-allow-origin"));
+    }
+
+    #[tokio::test]
+    async fn test_handler_panic_returns_internal_error() {
+        let config = RoutingConfig::builder()
+            .function(
+                "panic",
+                FunctionRoute::new(AccessLevel::Guest, box_route_handler(panicking_handler)),
+            )
+            .build()
+            .unwrap();
+        let request = ApiGatewayProxyRequest {
+            http_method: Method::POST,
+            path_parameters: [("proxy".to_string(), "panic".to_string())].into(),
+            ..Default::default()
+        };
+        let response = handle_route(&config, LambdaEvent::new(request, Context::default()))
+            .await
+            .unwrap();
+        assert_eq!(response.status_code, 500);
+        assert!(response.headers.contains_key("access-control-allow-origin"));
+    }
+
+    #[tokio::test]
+    async fn test_route_dimensions() {
+        let config = RoutingConfig::builder()
+            .function(
+                "panic",
+                FunctionRoute::new(AccessLevel::Guest, box_route_handler(panicking_handler)),
+            )
+            .build()
+            .unwrap();
+        let dimensions = |path: &str| {
+            let request = ApiGatewayProxyRequest {
+                http_method: Method::POST,
+                path_parameters: [("proxy".to_string(), path.to_string())].into(),
+                ..Default::default()
+            };
+            let event = LambdaEvent::new(request, Context::default());
+            let correlation_ids = CorrelationIds::from_event(&event);
+            let config = &config;
+            async move {
+                let (_, record) =
+                    with_metrics(dispatch_route(config, event, correlation_ids)).await;
+                let emf = record.to_emf("TestNamespace", 0);
+                (emf["Route"].clone(), emf["AccessLevel"].clone())
+            }
+        };
+
+        assert_eq!(dimensions("panic").await, ("panic".into(), "Guest".into()));
+        // Unmatched paths share a single dimension value.
+        assert_eq!(dimensions("unknown/1").await, ("-".into(), "-".into()));
+        assert_eq!(dimensions("unknown/2").await, ("-".into(), "-".into()));
+    }
+
+    #[tokio::test]
+    async fn test_route_resource() {
+        let config = RoutingConfig::builder()
+            .function("orders/create", ok_route(AccessLevel::Guest))
+            .build()
+            .unwrap();
+        // Per-route resource (as generated by SamTemplate), reached through a
+        // custom domain with a 'v1' base path mapping.
+        let request = ApiGatewayProxyRequest {
+            http_method: Method::POST,
+            resource: Some("/orders/create".to_string()),
+            path: Some("/v1/orders/create".to_string()),
+            ..Default::default()
+        };
+        let response = handle_route(&config, LambdaEvent::new(request, Context::default()))
+            .await
+            .unwrap();
+        assert_eq!(response.status_code, 200);
     }
 }
@@ -0,0 +1,518 @@
+use std::collections::{HashMap, HashSet};
+
+use fractic_server_error::ServerError;
+use serde_json::{json, Map, Value};
+
+use crate::{
+    builder::normalize_path,
+    constants::{
+        CORS_ALLOW_CREDENTIALS, CORS_ALLOW_HEADERS, CORS_ALLOW_METHODS, CORS_ALLOW_ORIGIN,
+        CORS_MAX_AGE_SECS,
+    },
+    errors::InvalidSamTemplateError,
+    routing::{AccessLevel, RoutingConfig},
+};
+
+// SAM template generation.
+// --------------------------------------------------
+//
+// Generates the SAM template fragment (the AWS::Serverless::Api and the
+// AWS::Serverless::Function serving it) for a RoutingConfig, for example:
+//
+//   let yaml = SamTemplate::new("OrdersFunction")
+//       .with_binary("orders")
+//       .with_crud_table("items", "ITEMS_TABLE", "ItemsTable")
+//       .to_yaml(&config)?;
+//
+// The function gets one API event per route and method (CRUD routes get one
+// per method not set to AccessLevel::None), and the Cognito authorizer is only
+// attached to events which require authentication. The API's CORS
+// configuration matches the headers set on responses by this crate.
+//
+// To generate the template from a binary, see
+// sam_template_from_routing_config!.
+
+const AUTHORIZER_NAME: &str = "CognitoAuthorizer";
+const USER_POOL_ARN_PARAMETER: &str = "UserPoolArn";
+
+pub struct SamTemplate {
+    function_name: String,
+    api_name: String,
+    stage_name: String,
+    binary: Option<String>,
+    binary_media_types: bool,
+    // If None, taken from the UserPoolArn template parameter.
+    user_pool_arn: Option<Value>,
+    environment: Vec<(String, Value)>,
+    // Route path, environment variable and logical ID of the table resource.
+    crud_tables: Vec<(String, String, String)>,
+}
+
+impl SamTemplate {
+    pub fn new(function_name: &str) -> Self {
+        SamTemplate {
+            function_name: function_name.to_string(),
+            api_name: "Api".to_string(),
+            stage_name: "prod".to_string(),
+            binary: None,
+            binary_media_types: false,
+            user_pool_arn: None,
+            environment: Vec::new(),
+            crud_tables: Vec::new(),
+        }
+    }
+
+    pub fn with_api_name(mut self, api_name: &str) -> Self {
+        self.api_name = api_name.to_string();
+        self
+    }
+
+    pub fn with_stage_name(mut self, stage_name: &str) -> Self {
+        self.stage_name = stage_name.to_string();
+        self
+    }
+
+    // Name of the cargo binary serving the routes, if the package has more
+    // than one.
+    pub fn with_binary(mut self, binary: &str) -> Self {
+        self.binary = Some(binary.to_string());
+        self
+    }
+
+    // Configures the API to decode base64-encoded response bodies, which is
+    // required for routes returning binary data (see FileResponse). Enabled
+    // automatically if the config has response compression.
+    pub fn with_binary_media_types(mut self) -> Self {
+        self.binary_media_types = true;
+        self
+    }
+
+    // ARN of the Cognito user pool, either as a literal or as an intrinsic
+    // function (for example json!({ "Fn::GetAtt": ["UserPool", "Arn"] })).
+    pub fn with_user_pool_arn(mut self, user_pool_arn: impl Into<Value>) -> Self {
+        self.user_pool_arn = Some(user_pool_arn.into());
+        self
+    }
+
+    pub fn with_environment_variable(mut self, name: &str, value: impl Into<Value>) -> Self {
+        self.environment.push((name.to_string(), value.into()));
+        self
+    }
+
+    // Table backing a CRUD route through CrudRouteScaffolding. The table name
+    // is passed in the given environment variable, and the function is granted
+    // CRUD access to the table.
+    pub fn with_crud_table(mut self, path: &str, env_var: &str, table_logical_id: &str) -> Self {
+        self.crud_tables.push((
+            path.to_string(),
+            env_var.to_string(),
+            table_logical_id.to_string(),
+        ));
+        self
+    }
+
+    // Fails if a CRUD table is configured for a path which is not a CRUD route
+    // of the config.
+    pub fn generate(&self, config: &RoutingConfig) -> Result<Value, ServerError> {
+        let mut problems = Vec::new();
+        let mut environment: Map<String, Value> = self.environment.iter().cloned().collect();
+        let mut policies = Vec::new();
+        let mut policy_tables = HashSet::new();
+        for (path, env_var, table_logical_id) in &self.crud_tables {
+            let normalized = normalize_path(path);
+            if !config.crud_routes().contains_key(&normalized) {
+                problems.push(format!(
+                    "table '{}' is configured for '{}', which is not a CRUD route",
+                    table_logical_id, normalized
+                ));
+                continue;
+            }
+            environment.insert(env_var.clone(), json!({ "Ref": table_logical_id }));
+            if policy_tables.insert(table_logical_id) {
+                policies.push(json!({
+                    "DynamoDBCrudPolicy": { "TableName": { "Ref": table_logical_id } },
+                }));
+            }
+        }
+        if !problems.is_empty() {
+            return Err(InvalidSamTemplateError::new(&problems.join("; ")));
+        }
+
+        // Sorted, so that the output is stable between runs.
+        let mut routes: Vec<(&String, Vec<(&str, AccessLevel)>)> = config
+            .function_routes()
+            .iter()
+            .map(|(path, route)| (path, vec![("post", route.access_level)]))
+            .chain(config.crud_routes().iter().map(|(path, route)| {
+                (
+                    path,
+                    vec![
+                        ("post", route.create_access_level),
+                        ("get", route.read_access_level),
+                        ("put", route.update_access_level),
+                        ("delete", route.delete_access_level),
+                    ],
+                )
+            }))
+            .collect();
+        routes.sort_by(|a, b| a.0.cmp(b.0));
+
+        let mut events = Map::new();
+        let mut event_name_counts: HashMap<String, usize> = HashMap::new();
+        let mut requires_authorizer = false;
+        for (path, methods) in routes {
+            for (method, access_level) in methods {
+                let requires_auth = match access_level {
+                    AccessLevel::Guest => false,
+                    AccessLevel::User | AccessLevel::Admin => true,
+                    AccessLevel::None | AccessLevel::Inherit => continue,
+                };
+                let mut properties = json!({
+                    "RestApiId": { "Ref": self.api_name },
+                    "Path": format!("/{}", path),
+                    "Method": method,
+                });
+                if requires_auth {
+                    requires_authorizer = true;
+                    properties["Auth"] = json!({ "Authorizer": AUTHORIZER_NAME });
+                }
+                let mut event_name = format!("{}{}", pascal_case(path), pascal_case(method));
+                let count = event_name_counts.entry(event_name.clone()).or_default();
+                *count += 1;
+                if *count > 1 {
+                    event_name = format!("{}{}", event_name, count);
+                }
+                events.insert(
+                    event_name,
+                    json!({ "Type": "Api", "Properties": properties }),
+                );
+            }
+        }
+
+        let mut api_auth = json!({
+            "AddApiKeyRequiredToCorsPreflight": false,
+            "AddDefaultAuthorizerToCorsPreflight": false,
+        });
+        if requires_authorizer {
+            let user_pool_arn = self
+                .user_pool_arn
+                .clone()
+                .unwrap_or_else(|| json!({ "Ref": USER_POOL_ARN_PARAMETER }));
+            api_auth["Authorizers"] = json!({ AUTHORIZER_NAME: { "UserPoolArn": user_pool_arn } });
+        }
+        let mut api = json!({
+            "Type": "AWS::Serverless::Api",
+            "Properties": {
+                "StageName": self.stage_name,
+                "Cors": {
+                    "AllowMethods": format!("'{}'", CORS_ALLOW_METHODS),
+                    "AllowHeaders": format!("'{}'", CORS_ALLOW_HEADERS),
+                    "AllowOrigin": format!("'{}'", CORS_ALLOW_ORIGIN),
+                    "MaxAge": format!("'{}'", CORS_MAX_AGE_SECS),
+                    "AllowCredentials": CORS_ALLOW_CREDENTIALS,
+                },
+                "Auth": api_auth,
+            },
+        });
+
+        if self.binary_media_types || config.compression().is_some() {
+            // Required for API Gateway to decode base64-encoded response
+            // bodies ("~1" escapes "/").
+            api["Properties"]["BinaryMediaTypes"] = json!(["*~1*"]);
+        }
+
+        let mut function_properties = json!({
+            "CodeUri": ".",
+            "Handler": "bootstrap",
+            "Runtime": "provided.al2023",
+            "Events": events,
+        });
+        if !environment.is_empty() {
+            function_properties["Environment"] = json!({ "Variables": environment });
+        }
+        if !policies.is_empty() {
+            function_properties["Policies"] = policies.into();
+        }
+        let mut function = json!({
+            "Type": "AWS::Serverless::Function",
+            "Metadata": { "BuildMethod": "rust-cargolambda" },
+            "Properties": function_properties,
+        });
+        if let Some(binary) = &self.binary {
+            function["Metadata"]["BuildProperties"] = json!({ "Binary": binary });
+        }
+
+        let mut template = json!({
+            "Resources": {
+                &self.api_name: api,
+                &self.function_name: function,
+            },
+        });
+        if requires_authorizer && self.user_pool_arn.is_none() {
+            template["Parameters"] = json!({
+                USER_POOL_ARN_PARAMETER: {
+                    "Type": "String",
+                    "Description": "ARN of the Cognito user pool used to authenticate requests.",
+                },
+            });
+        }
+        Ok(template)
+    }
+
+    pub fn to_yaml(&self, config: &RoutingConfig) -> Result<String, ServerError> {
+        let mut yaml = String::new();
+        write_yaml(&self.generate(config)?, 0, &mut yaml);
+        Ok(yaml)
+    }
+}
+
+// Helper functions.
+// --------------------------------------------------
+
+// "orders/create-bulk" -> "OrdersCreateBulk", for use in logical IDs (which
+// must be alphanumeric).
+fn pascal_case(path: &str) -> String {
+    path.split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_ascii_uppercase().to_string() + chars.as_str())
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+// Minimal YAML serialization of a JSON value, enough for CloudFormation
+// templates. Strings are written plain where unambiguous, and otherwise as
+// JSON strings (which are valid YAML double-quoted scalars).
+fn write_yaml(value: &Value, indent: usize, out: &mut String) {
+    let padding = " ".repeat(indent);
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                out.push_str(&format!("{}{}:", padding, yaml_scalar(key)));
+                write_yaml_child(value, indent, out);
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                match item {
+                    // Start the first line of nested collections after the
+                    // dash, as in "- Key: value" or "- - value".
+                    Value::Object(map) if !map.is_empty() => write_yaml_item(item, indent, out),
+                    Value::Array(items) if !items.is_empty() => write_yaml_item(item, indent, out),
+                    _ => out.push_str(&format!("{}- {}\n", padding, yaml_value(item))),
+                }
+            }
+        }
+        _ => out.push_str(&format!("{}{}\n", padding, yaml_value(value))),
+    }
+}
+
+fn write_yaml_item(item: &Value, indent: usize, out: &mut String) {
+    let mut nested = String::new();
+    write_yaml(item, indent + 2, &mut nested);
+    out.push_str(&format!(
+        "{}- {}",
+        " ".repeat(indent),
+        &nested[indent + 2..]
+    ));
+}
+
+fn write_yaml_child(value: &Value, indent: usize, out: &mut String) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            out.push('\n');
+            write_yaml(value, indent + 2, out);
+        }
+        Value::Array(items) if !items.is_empty() => {
+            out.push('\n');
+            write_yaml(value, indent + 2, out);
+        }
+        _ => out.push_str(&format!(" {}\n", yaml_value(value))),
+    }
+}
+
+fn yaml_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => yaml_scalar(s),
+        Value::Object(_) => "{}".to_string(),
+        Value::Array(_) => "[]".to_string(),
+        other => other.to_string(),
+    }
+}
+
+fn yaml_scalar(s: &str) -> String {
+    const RESERVED: [&str; 10] = [
+        "true", "false", "yes", "no", "on", "off", "null", "y", "n", "~",
+    ];
+    let is_plain = s
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '/')
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-./:+{}".contains(c))
+        && !s.ends_with(':')
+        && !RESERVED.contains(&s.to_ascii_lowercase().as_str());
+    match is_plain {
+        true => s.to_string(),
+        false => Value::String(s.to_string()).to_string(),
+    }
+}
+
+// Tests.
+// --------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        routing::{box_route_handler, CrudRoute},
+        testing::{ok_handler, ok_route},
+    };
+
+    fn create_config() -> RoutingConfig {
+        RoutingConfig::builder()
+            .function("orders/create", ok_route(AccessLevel::User))
+            .function("health", ok_route(AccessLevel::Guest))
+            .crud(
+                "items",
+                CrudRoute {
+                    create_access_level: AccessLevel::Admin,
+                    read_access_level: AccessLevel::Guest,
+                    update_access_level: AccessLevel::Admin,
+                    delete_access_level: AccessLevel::None,
+                    handler: box_route_handler(ok_handler),
+                    timeout: None,
+                    body_config: Default::default(),
+                    field_selection: Default::default(),
+                    cache_control: None,
+                },
+            )
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_generate() {
+        let template = SamTemplate::new("OrdersFunction")
+            .with_crud_table("/items/", "ITEMS_TABLE", "ItemsTable")
+            .generate(&create_config())
+            .unwrap();
+        let function = &template["Resources"]["OrdersFunction"]["Properties"];
+        let events = function["Events"].as_object().unwrap();
+        let mut event_names = events.keys().collect::<Vec<_>>();
+        event_names.sort();
+        assert_eq!(
+            event_names,
+            vec![
+                "HealthPost",
+                "ItemsGet",
+                "ItemsPost",
+                "ItemsPut",
+                "OrdersCreatePost"
+            ]
+        );
+        assert_eq!(
+            events["OrdersCreatePost"]["Properties"]["Auth"]["Authorizer"],
+            AUTHORIZER_NAME
+        );
+        assert!(events["HealthPost"]["Properties"].get("Auth").is_none());
+        assert!(events["ItemsGet"]["Properties"].get("Auth").is_none());
+        assert_eq!(
+            function["Environment"]["Variables"]["ITEMS_TABLE"],
+            json!({ "Ref": "ItemsTable" })
+        );
+
+        let api = &template["Resources"]["Api"]["Properties"];
+        assert_eq!(api["Cors"]["AllowOrigin"], "'https://fractic.io'");
+        assert_eq!(
+            api["Auth"]["Authorizers"][AUTHORIZER_NAME]["UserPoolArn"],
+            json!({ "Ref": USER_POOL_ARN_PARAMETER })
+        );
+        assert!(template["Parameters"][USER_POOL_ARN_PARAMETER].is_object());
+        assert!(api.get("BinaryMediaTypes").is_none());
+    }
+
+    #[test]
+    fn test_authorizer_omitted_for_guest_routes() {
+        let config = RoutingConfig::builder()
+            .function("health", ok_route(AccessLevel::Guest))
+            .build()
+            .unwrap();
+        let template = SamTemplate::new("Function").generate(&config).unwrap();
+        assert!(template["Resources"]["Api"]["Properties"]["Auth"]
+            .get("Authorizers")
+            .is_none());
+        assert!(template.get("Parameters").is_none());
+    }
+
+    #[test]
+    fn test_binary_media_types_with_compression() {
+        let config = RoutingConfig::builder()
+            .function("health", ok_route(AccessLevel::Guest))
+            .compression(Default::default())
+            .build()
+            .unwrap();
+        let template = SamTemplate::new("Function").generate(&config).unwrap();
+        assert_eq!(
+            template["Resources"]["Api"]["Properties"]["BinaryMediaTypes"],
+            json!(["*~1*"])
+        );
+    }
+
+    #[test]
+    fn test_unknown_crud_table_rejected() {
+        let err = SamTemplate::new("Function")
+            .with_crud_table("orders/create", "ORDERS_TABLE", "OrdersTable")
+            .generate(&create_config())
+            .unwrap_err();
+        assert!(format!("{:?}", err).contains("'orders/create', which is not a CRUD route"));
+    }
+
+    #[test]
+    fn test_to_yaml() {
+        let mut yaml = String::new();
+        write_yaml(
+            &json!({
+                "Cors": { "AllowCredentials": true, "AllowOrigin": "'*'" },
+                "Events": {},
+                "Flag": "yes",
+                "Path": "/orders/create",
+                "Policies": [{ "DynamoDBCrudPolicy": { "TableName": { "Ref": "T" } } }],
+                "UserPoolArn": {
+                    "Fn::Join": ["", [
+                        "arn:aws:cognito-idp:",
+                        { "Ref": "AWS::Region" },
+                        ":",
+                        { "Ref": "AWS::AccountId" },
+                    ]],
+                },
+            }),
+            0,
+            &mut yaml,
+        );
+        let expected = [
+            "Cors:",
+            "  AllowCredentials: true",
+            "  AllowOrigin: \"'*'\"",
+            "Events: {}",
+            "Flag: \"yes\"",
+            "Path: /orders/create",
+            "Policies:",
+            "  - DynamoDBCrudPolicy:",
+            "      TableName:",
+            "        Ref: T",
+            "UserPoolArn:",
+            "  Fn::Join:",
+            "    - \"\"",
+            "    - - \"arn:aws:cognito-idp:\"",
+            "      - Ref: AWS::Region",
+            "      - \":\"",
+            "      - Ref: AWS::AccountId",
+        ];
+        assert_eq!(yaml.lines().collect::<Vec<_>>(), expected);
+    }
+}
@@ -0,0 +1,64 @@
+use std::marker::PhantomData;
+
+// Request schemas.
+// --------------------------------------------------
+//
+// Schema of a function route's request data, so that the OpenAPI document
+// (see openapi.rs) describes the request body without repeating the type in
+// RouteDocs. #[route] captures it from the request data type. For routes
+// registered with register_function_route!, it is attached when constructing
+// the FunctionRoute:
+//
+//   FunctionRoute::new(AccessLevel::User, box_route_handler(create_order_handler))
+//       .with_request_schema(request_schema_of!(CreateOrder))
+//
+// Schemas are only recorded with the 'openapi' feature, and only for types
+// implementing JsonSchema. RouteDocs::request overrides the captured schema.
+
+#[derive(Clone, Copy, Default)]
+pub struct RequestSchema {
+    #[cfg(feature = "openapi")]
+    pub(crate) schema_fn: Option<crate::openapi::SchemaFn>,
+}
+
+impl RequestSchema {
+    pub fn none() -> Self {
+        RequestSchema::default()
+    }
+
+    #[cfg(feature = "openapi")]
+    pub fn of<T: schemars::JsonSchema>() -> Self {
+        RequestSchema {
+            schema_fn: Some(|gen| gen.subschema_for::<T>()),
+        }
+    }
+}
+
+// Used by the registration macros to capture the schema only for request data
+// types which implement JsonSchema (through autoref-based specialization, as
+// for ValidationWrap).
+#[doc(hidden)]
+pub struct SchemaWrap<T>(pub PhantomData<T>);
+
+#[doc(hidden)]
+pub trait SchemaIfImplemented {
+    fn schema_if_implemented(&self) -> RequestSchema;
+}
+
+#[cfg(feature = "openapi")]
+impl<T: schemars::JsonSchema> SchemaIfImplemented for SchemaWrap<T> {
+    fn schema_if_implemented(&self) -> RequestSchema {
+        RequestSchema::of::<T>()
+    }
+}
+
+#[doc(hidden)]
+pub trait SchemaFallback {
+    fn schema_if_implemented(&self) -> RequestSchema;
+}
+
+impl<T> SchemaFallback for &SchemaWrap<T> {
+    fn schema_if_implemented(&self) -> RequestSchema {
+        RequestSchema::none()
+    }
+}
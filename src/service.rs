@@ -0,0 +1,304 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context as TaskContext, Poll},
+};
+
+use aws_lambda_events::{
+    apigw::{ApiGatewayProxyRequest, ApiGatewayProxyRequestContext, ApiGatewayProxyResponse},
+    encodings::Body,
+    http::{Extensions, Request, Response, StatusCode},
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use lambda_runtime::{Context, Error, LambdaEvent};
+use tower_service::Service;
+
+use crate::{
+    builder::normalize_path,
+    routing::{handle_route, RouteFuture, RoutingConfig},
+};
+
+// Tower service.
+// --------------------------------------------------
+//
+// Router exposes a RoutingConfig as a tower::Service, so that standard tower
+// layers (timeouts, concurrency limits, tracing, etc.) can be stacked on top
+// of it. It implements Service for both:
+//
+//   - LambdaEvent<ApiGatewayProxyRequest>, for lambda_runtime::run, and
+//   - http::Request<Body>, for lambda_http::run or a regular HTTP server.
+//     Requests are adapted to ApiGatewayProxyRequest, so existing handlers
+//     work unchanged. The Lambda Context and the API Gateway request context
+//     (authorizer claims) are taken from the request extensions, if present.
+//     lambda_http stores the request context wrapped in its own RequestContext
+//     type, which is only read with the 'lambda-http' feature enabled.
+//
+// Cloning a Router is cheap, since the config is shared.
+
+#[derive(Clone)]
+pub struct Router {
+    config: Arc<RoutingConfig>,
+    base_path: String,
+}
+
+impl Router {
+    pub fn new(config: RoutingConfig) -> Self {
+        Router::from_shared(Arc::new(config))
+    }
+
+    pub fn from_shared(config: Arc<RoutingConfig>) -> Self {
+        Router {
+            config,
+            base_path: String::new(),
+        }
+    }
+
+    // Prefix stripped from http::Request paths before routing (for example
+    // the API Gateway stage, or a mount point such as "/api").
+    pub fn with_base_path(mut self, base_path: &str) -> Self {
+        self.base_path = normalize_path(base_path);
+        self
+    }
+
+    pub fn config(&self) -> &RoutingConfig {
+        &self.config
+    }
+}
+
+impl RoutingConfig {
+    pub fn into_service(self) -> Router {
+        Router::new(self)
+    }
+}
+
+impl Service<LambdaEvent<ApiGatewayProxyRequest>> for Router {
+    type Response = ApiGatewayProxyResponse;
+    type Error = Error;
+    type Future = RouteFuture;
+
+    fn poll_ready(&mut self, _: &mut TaskContext<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, event: LambdaEvent<ApiGatewayProxyRequest>) -> Self::Future {
+        let config = self.config.clone();
+        Box::pin(async move { handle_route(&config, event).await })
+    }
+}
+
+impl Service<Request<Body>> for Router {
+    type Response = Response<Body>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Response<Body>, Error>> + Send>>;
+
+    fn poll_ready(&mut self, _: &mut TaskContext<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        let config = self.config.clone();
+        let event = http_request_to_event(request, &self.base_path);
+        Box::pin(async move {
+            let response = handle_route(&config, event).await?;
+            proxy_response_to_http(response)
+        })
+    }
+}
+
+// Adapter utils.
+// --------------------------------------------------
+
+pub fn http_request_to_event(
+    request: Request<Body>,
+    base_path: &str,
+) -> LambdaEvent<ApiGatewayProxyRequest> {
+    let (parts, body) = request.into_parts();
+    let context = parts
+        .extensions
+        .get::<Context>()
+        .cloned()
+        .unwrap_or_default();
+    let request_context = request_context(&parts.extensions);
+
+    let path = normalize_path(parts.uri.path());
+    let proxy = match path.strip_prefix(base_path) {
+        Some(rest) if base_path.is_empty() || rest.is_empty() || rest.starts_with('/') => {
+            normalize_path(rest)
+        }
+        _ => path.clone(),
+    };
+
+    let mut query: HashMap<String, Vec<String>> = HashMap::new();
+    for (key, value) in form_urlencoded::parse(parts.uri.query().unwrap_or_default().as_bytes()) {
+        query
+            .entry(key.into_owned())
+            .or_default()
+            .push(value.into_owned());
+    }
+    let single_value_query: HashMap<String, String> = query
+        .iter()
+        .filter_map(|(k, v)| Some((k.clone(), v.last()?.clone())))
+        .collect();
+
+    let (body, is_base64_encoded) = match body {
+        Body::Empty => (None, false),
+        Body::Text(text) => (Some(text), false),
+        Body::Binary(bytes) => match String::from_utf8(bytes) {
+            Ok(text) => (Some(text), false),
+            Err(e) => (Some(BASE64.encode(e.into_bytes())), true),
+        },
+    };
+
+    let payload = ApiGatewayProxyRequest {
+        path: Some(format!("/{}", path)),
+        http_method: parts.method,
+        headers: parts.headers.clone(),
+        multi_value_headers: parts.headers,
+        query_string_parameters: single_value_query.into(),
+        multi_value_query_string_parameters: query.into(),
+        path_parameters: [("proxy".to_string(), proxy)].into(),
+        request_context,
+        body,
+        is_base64_encoded,
+        ..Default::default()
+    };
+    LambdaEvent::new(payload, context)
+}
+
+pub fn proxy_response_to_http(response: ApiGatewayProxyResponse) -> Result<Response<Body>, Error> {
+    let body = match response.body {
+        Some(Body::Text(text)) if response.is_base64_encoded => Body::Binary(BASE64.decode(text)?),
+        Some(body) => body,
+        None => Body::Empty,
+    };
+    let mut http_response = Response::new(body);
+    *http_response.status_mut() = StatusCode::from_u16(u16::try_from(response.status_code)?)?;
+    let headers = http_response.headers_mut();
+    for (name, value) in response.multi_value_headers.iter() {
+        headers.append(name, value.clone());
+    }
+    for (name, value) in response.headers.iter() {
+        if !headers.contains_key(name) {
+            headers.insert(name, value.clone());
+        }
+    }
+    Ok(http_response)
+}
+
+// Helper functions.
+// --------------------------------------------------
+
+fn request_context(extensions: &Extensions) -> ApiGatewayProxyRequestContext {
+    if let Some(context) = extensions.get::<ApiGatewayProxyRequestContext>() {
+        return context.clone();
+    }
+    #[cfg(feature = "lambda-http")]
+    if let Some(lambda_http::request::RequestContext::ApiGatewayV1(context)) = extensions.get() {
+        return context.clone();
+    }
+    ApiGatewayProxyRequestContext::default()
+}
+
+// Tests.
+// --------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        request::RequestMetadata,
+        routing::{box_route_handler, AccessLevel, FunctionRoute},
+    };
+    use aws_lambda_events::http::Method;
+
+    async fn echo_handler(
+        event: LambdaEvent<ApiGatewayProxyRequest>,
+        _: RequestMetadata,
+    ) -> Result<ApiGatewayProxyResponse, Error> {
+        let request = event.payload;
+        crate::build_result(format!(
+            "{} {:?} {:?}",
+            request.path_parameters["proxy"],
+            request.query_string_parameters.first("q"),
+            request.body
+        ))
+    }
+
+    fn create_router() -> Router {
+        RoutingConfig::builder()
+            .function(
+                "orders/echo",
+                FunctionRoute::new(AccessLevel::Guest, box_route_handler(echo_handler)),
+            )
+            .build()
+            .unwrap()
+            .into_service()
+            .with_base_path("/api/")
+    }
+
+    #[tokio::test]
+    async fn test_http_request_routed() {
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("https://example.com/api/orders/echo?q=1&q=2")
+            .body(Body::from("{}"))
+            .unwrap();
+        let response = create_router().call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response
+            .headers()
+            .contains_key("access-control-allow-origin"));
+        let body = String::from_utf8(response.body().to_vec()).unwrap();
+        assert!(body.contains("orders/echo Some(\\\"2\\\") Some(\\\"{}\\\")"));
+    }
+
+    #[tokio::test]
+    async fn test_http_unknown_route() {
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/api/orders/missing")
+            .body(Body::Empty)
+            .unwrap();
+        let response = create_router().call(request).await.unwrap();
+        let body = String::from_utf8(response.body().to_vec()).unwrap();
+        assert!(body.contains("\"ok\":false"));
+    }
+
+    #[cfg(feature = "lambda-http")]
+    #[test]
+    fn test_lambda_http_request_context() {
+        use crate::auth::{fake_cognito_claims, is_authenticated};
+        use lambda_http::request::LambdaRequest;
+
+        let mut proxy_request = ApiGatewayProxyRequest {
+            path: Some("/api/orders/echo".to_string()),
+            http_method: Method::GET,
+            ..Default::default()
+        };
+        proxy_request.request_context.authorizer.fields.insert(
+            "claims".to_string(),
+            fake_cognito_claims("sub-1", "jane", &[]),
+        );
+        // Converted the same way as by lambda_http::run.
+        let request: lambda_http::Request = LambdaRequest::ApiGatewayV1(proxy_request).into();
+
+        let event = http_request_to_event(request, "api");
+        assert_eq!(event.payload.path_parameters["proxy"], "orders/echo");
+        assert!(is_authenticated(&event.payload));
+    }
+
+    #[test]
+    fn test_base64_response_decoded() {
+        let response = ApiGatewayProxyResponse {
+            status_code: 201,
+            body: Some(Body::Text(BASE64.encode([0xff, 0x00]))),
+            is_base64_encoded: true,
+            ..Default::default()
+        };
+        let http_response = proxy_response_to_http(response).unwrap();
+        assert_eq!(http_response.status(), StatusCode::CREATED);
+        assert_eq!(http_response.body().to_vec(), vec![0xff, 0x00]);
+    }
+}
@@ -0,0 +1,459 @@
+use aws_lambda_events::{
+    apigw::{
+        ApiGatewayProxyRequest, ApiGatewayProxyRequestContext, ApiGatewayProxyResponse,
+        ApiGatewayRequestAuthorizer,
+    },
+    encodings::Body,
+    http::{
+        header::{CONTENT_TYPE, SET_COOKIE},
+        HeaderMap, HeaderName, HeaderValue, Method, Request,
+    },
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use lambda_runtime::{Context, LambdaEvent};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+use crate::{
+    auth::fake_cognito_claims,
+    client::{RequestSigner, SignerFuture},
+    response::ResponseWrapper,
+    routing::{handle_route, RoutingConfig},
+};
+
+// Test harness.
+// --------------------------------------------------
+//
+// Helpers for invoking routes in-process from tests (requires the 'testing'
+// feature), for example:
+//
+//   let client = TestClient::new(config);
+//   let response = client
+//       .send(TestRequest::post("orders/create").json(&order).as_user("sub-1"))
+//       .await;
+//   let created: Order = response.data();
+//
+// Requests go through handle_route, so access levels, body config, validation
+// and response building behave as in production.
+
+const TEST_USER_SUB: &str = "test-user";
+
+pub struct TestRequest {
+    method: Method,
+    path: String,
+    query: Vec<(String, String)>,
+    headers: HeaderMap,
+    body: Option<String>,
+    claims: Option<Value>,
+    context: Context,
+}
+
+impl TestRequest {
+    pub fn new(method: Method, path: &str) -> Self {
+        TestRequest {
+            method,
+            path: path.to_string(),
+            query: Vec::new(),
+            headers: HeaderMap::new(),
+            body: None,
+            claims: None,
+            context: Context::default(),
+        }
+    }
+
+    pub fn get(path: &str) -> Self {
+        TestRequest::new(Method::GET, path)
+    }
+
+    pub fn post(path: &str) -> Self {
+        TestRequest::new(Method::POST, path)
+    }
+
+    pub fn put(path: &str) -> Self {
+        TestRequest::new(Method::PUT, path)
+    }
+
+    pub fn delete(path: &str) -> Self {
+        TestRequest::new(Method::DELETE, path)
+    }
+
+    pub fn query(mut self, key: &str, value: &str) -> Self {
+        self.query.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    // Panics if the name or value is not a valid header, since that is a
+    // mistake in the test itself.
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.append(
+            HeaderName::from_bytes(name.as_bytes()).expect("invalid header name"),
+            HeaderValue::from_str(value).expect("invalid header value"),
+        );
+        self
+    }
+
+    pub fn json(mut self, body: &impl Serialize) -> Self {
+        self.body = Some(serde_json::to_string(body).expect("failed to serialize body"));
+        self.headers
+            .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        self
+    }
+
+    pub fn body(mut self, body: &str) -> Self {
+        self.body = Some(body.to_string());
+        self
+    }
+
+    pub fn context(mut self, context: Context) -> Self {
+        self.context = context;
+        self
+    }
+
+    pub fn as_guest(mut self) -> Self {
+        self.claims = None;
+        self
+    }
+
+    pub fn as_user(mut self, sub: &str) -> Self {
+        self.claims = Some(fake_cognito_claims(sub, sub, &[]));
+        self
+    }
+
+    // Authenticates as a test user if not already, and adds the admin group.
+    pub fn as_admin(self) -> Self {
+        self.with_groups(&["admin"])
+    }
+
+    // Authenticates as a test user if not already, and adds the groups.
+    pub fn with_groups(self, groups: &[&str]) -> Self {
+        let mut request = match self.claims {
+            Some(_) => self,
+            None => self.as_user(TEST_USER_SUB),
+        };
+        if let Some(Value::Object(claims)) = &mut request.claims {
+            let mut all_groups: Vec<String> = claims
+                .get("cognito:groups")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .split(',')
+                .filter(|g| !g.is_empty())
+                .map(str::to_string)
+                .collect();
+            all_groups.extend(groups.iter().map(|g| g.to_string()));
+            claims.insert("cognito:groups".to_string(), all_groups.join(",").into());
+        }
+        request
+    }
+
+    // Replaces the authorizer claims entirely.
+    pub fn with_claims(mut self, claims: Value) -> Self {
+        self.claims = Some(claims);
+        self
+    }
+
+    pub fn into_event(self) -> LambdaEvent<ApiGatewayProxyRequest> {
+        let mut query: HashMap<String, Vec<String>> = HashMap::new();
+        for (key, value) in self.query {
+            query.entry(key).or_default().push(value);
+        }
+        let single_value_query: HashMap<String, String> = query
+            .iter()
+            .filter_map(|(k, v)| Some((k.clone(), v.last()?.clone())))
+            .collect();
+        let path = self.path.trim_matches('/').to_string();
+        let payload = ApiGatewayProxyRequest {
+            path: Some(format!("/{}", path)),
+            http_method: self.method.clone(),
+            headers: self.headers.clone(),
+            multi_value_headers: self.headers,
+            query_string_parameters: single_value_query.into(),
+            multi_value_query_string_parameters: query.into(),
+            path_parameters: [("proxy".to_string(), path)].into(),
+            request_context: ApiGatewayProxyRequestContext {
+                http_method: self.method,
+                authorizer: ApiGatewayRequestAuthorizer {
+                    fields: self
+                        .claims
+                        .map(|claims| ("claims".to_string(), claims))
+                        .into_iter()
+                        .collect(),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            body: self.body,
+            ..Default::default()
+        };
+        LambdaEvent::new(payload, self.context)
+    }
+}
+
+// Runs a RoutingConfig in-process.
+pub struct TestClient {
+    config: RoutingConfig,
+}
+
+impl TestClient {
+    pub fn new(config: RoutingConfig) -> Self {
+        TestClient { config }
+    }
+
+    // Panics if handle_route returns an error, which only happens if the
+    // response could not be built.
+    pub async fn send(&self, request: TestRequest) -> TestResponse {
+        let response = handle_route(&self.config, request.into_event())
+            .await
+            .expect("handle_route failed");
+        TestResponse { response }
+    }
+}
+
+#[derive(Debug)]
+pub struct TestResponse {
+    pub response: ApiGatewayProxyResponse,
+}
+
+impl TestResponse {
+    pub fn status(&self) -> i64 {
+        self.response.status_code
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.response
+            .headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+    }
+
+    pub fn text(&self) -> String {
+        match &self.response.body {
+            Some(Body::Text(text)) => text.clone(),
+            Some(Body::Binary(bytes)) => String::from_utf8_lossy(bytes).into_owned(),
+            _ => String::new(),
+        }
+    }
+
+    // Raw body, decoded from base64 if needed (for example for FileResponse).
+    pub fn bytes(&self) -> Vec<u8> {
+        match &self.response.body {
+            Some(Body::Text(text)) if self.response.is_base64_encoded => BASE64
+                .decode(text)
+                .unwrap_or_else(|e| panic!("response body is not valid base64: {}", e)),
+            Some(Body::Text(text)) => text.as_bytes().to_vec(),
+            Some(Body::Binary(bytes)) => bytes.clone(),
+            _ => Vec::new(),
+        }
+    }
+
+    // Values of the Set-Cookie headers (see ApiResponse::with_cookie).
+    pub fn cookies(&self) -> Vec<&str> {
+        self.response
+            .multi_value_headers
+            .get_all(SET_COOKIE)
+            .iter()
+            .chain(self.response.headers.get_all(SET_COOKIE))
+            .filter_map(|v| v.to_str().ok())
+            .collect()
+    }
+
+    // True if the route returned data (as opposed to an error).
+    pub fn is_ok(&self) -> bool {
+        (200..300).contains(&self.status())
+            && (self.response.body.is_none() || self.wrapper::<Value>().is_some_and(|w| w.ok))
+    }
+
+    // Decodes the response data. Panics with the error if the route failed.
+    pub fn data<T: DeserializeOwned>(&self) -> T {
+        match self.wrapper::<T>() {
+            Some(ResponseWrapper {
+                ok: true,
+                data: Some(data),
+                ..
+            }) => data,
+            _ => panic!(
+                "expected successful response, got {}: {}",
+                self.status(),
+                self.text()
+            ),
+        }
+    }
+
+    // Message of the error sent to the client, if the route failed. For
+    // forwarded errors this is the wrapped message, and for 401 / 500
+    // responses the plain-text body.
+    pub fn error(&self) -> Option<String> {
+        if !(200..300).contains(&self.status()) {
+            return Some(self.text());
+        }
+        self.wrapper::<Value>().and_then(|w| w.error)
+    }
+
+    pub fn assert_ok(&self) -> &Self {
+        assert!(
+            self.is_ok(),
+            "expected successful response, got {}: {}",
+            self.status(),
+            self.text()
+        );
+        self
+    }
+
+    pub fn assert_status(&self, status: i64) -> &Self {
+        assert_eq!(self.status(), status, "unexpected status: {}", self.text());
+        self
+    }
+
+    pub fn assert_error_contains(&self, text: &str) -> &Self {
+        match self.error() {
+            Some(error) => assert!(
+                error.contains(text),
+                "expected error containing '{}', got '{}'",
+                text,
+                error
+            ),
+            None => panic!(
+                "expected error containing '{}', got successful response: {}",
+                text,
+                self.text()
+            ),
+        }
+        self
+    }
+
+    fn wrapper<T: DeserializeOwned>(&self) -> Option<ResponseWrapper<T>> {
+        serde_json::from_str(&self.text()).ok()
+    }
+}
+
+// Signer for ApiClient requests sent in-process (for example through Router),
+// which attaches the authorizer claims that API Gateway would otherwise add.
+pub struct FakeIdentity {
+    claims: Value,
+}
+
+impl FakeIdentity {
+    pub fn user(sub: &str) -> Self {
+        FakeIdentity::with_claims(fake_cognito_claims(sub, sub, &[]))
+    }
+
+    pub fn admin(sub: &str) -> Self {
+        FakeIdentity::with_claims(fake_cognito_claims(sub, sub, &["admin"]))
+    }
+
+    pub fn with_claims(claims: Value) -> Self {
+        FakeIdentity { claims }
+    }
+}
+
+impl RequestSigner for FakeIdentity {
+    fn sign<'a>(&'a self, request: &'a mut Request<Body>) -> SignerFuture<'a> {
+        Box::pin(async move {
+            let context = ApiGatewayProxyRequestContext {
+                http_method: request.method().clone(),
+                authorizer: ApiGatewayRequestAuthorizer {
+                    fields: [("claims".to_string(), self.claims.clone())].into(),
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            request.extensions_mut().insert(context);
+            Ok(())
+        })
+    }
+}
+
+// Test fixtures.
+// --------------------------------------------------
+//
+// Route shared by the crate's tests, answering "ok".
+
+#[cfg(test)]
+pub(crate) async fn ok_handler(
+    _: LambdaEvent<ApiGatewayProxyRequest>,
+    _: crate::RequestMetadata,
+) -> Result<ApiGatewayProxyResponse, lambda_runtime::Error> {
+    crate::build_result("ok")
+}
+
+#[cfg(test)]
+pub(crate) fn ok_route(access_level: crate::AccessLevel) -> crate::FunctionRoute {
+    crate::FunctionRoute::new(access_level, crate::box_route_handler(ok_handler))
+}
+
+// Tests.
+// --------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        request::RequestMetadata,
+        routing::{box_route_handler, AccessLevel, FunctionRoute},
+    };
+    use lambda_runtime::Error;
+
+    async fn whoami(
+        _: LambdaEvent<ApiGatewayProxyRequest>,
+        metadata: RequestMetadata,
+    ) -> Result<ApiGatewayProxyResponse, Error> {
+        crate::build_result(metadata.user_sub)
+    }
+
+    fn create_client(access_level: AccessLevel) -> TestClient {
+        TestClient::new(
+            RoutingConfig::builder()
+                .function(
+                    "whoami",
+                    FunctionRoute::new(access_level, box_route_handler(whoami)),
+                )
+                .build()
+                .unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_identities() {
+        let client = create_client(AccessLevel::Admin);
+        let response = client
+            .send(TestRequest::post("/whoami/").as_user("sub-1").as_admin())
+            .await;
+        response.assert_ok();
+        assert_eq!(response.data::<String>(), "sub-1");
+
+        client
+            .send(TestRequest::post("whoami").as_user("sub-1"))
+            .await
+            .assert_status(401);
+        client
+            .send(TestRequest::post("whoami").as_guest())
+            .await
+            .assert_status(401);
+    }
+
+    #[tokio::test]
+    async fn test_error_decoded() {
+        let client = create_client(AccessLevel::Guest);
+        let response = client.send(TestRequest::post("missing")).await;
+        assert!(!response.is_ok());
+        response
+            .assert_status(200)
+            .assert_error_contains("invalid request");
+    }
+
+    #[test]
+    fn test_with_groups_merges() {
+        let event = TestRequest::get("items")
+            .with_groups(&["editors"])
+            .as_admin()
+            .query("page", "2")
+            .into_event();
+        let claims = &event.payload.request_context.authorizer.fields["claims"];
+        assert_eq!(claims["sub"], TEST_USER_SUB);
+        assert_eq!(claims["cognito:groups"], "editors,admin");
+        assert_eq!(
+            event.payload.query_string_parameters.first("page"),
+            Some("2")
+        );
+    }
+}
@@ -0,0 +1,492 @@
+use fractic_server_error::ServerError;
+use serde_json::Value;
+
+use crate::{openapi::OpenApi, routing::RoutingConfig};
+
+// TypeScript client generation.
+// --------------------------------------------------
+//
+// Generates a typed TypeScript client module from the OpenAPI document of a
+// RoutingConfig (requires the 'openapi' feature), for example:
+//
+//   let source = OpenApi::new("Orders API", "1.0.0")
+//       .route("orders/create", RouteDocs::new().request::<CreateOrder>().response::<Order>())
+//       .generate_typescript_client(&config)?;
+//
+// The module exports one type per documented schema, and a createClient
+// function returning one method per function route and CRUD operation:
+//
+//   const api = createClient({ baseUrl, getToken: () => session.idToken });
+//   const order: Order = await api.ordersCreate({ item: "book", quantity: 1 });
+//
+// Methods unwrap the ResponseWrapper envelope, throwing an ApiError if the
+// request failed (either with an error forwarded to the client, or with a
+// non-2xx status). The token returned by getToken is sent in the
+// Authorization header to routes which accept authentication.
+//
+// Output only depends on the document (paths, methods and schemas are sorted),
+// so it can be checked into the client repository and regenerated in CI.
+
+const CLIENT_RUNTIME: &str = r#"export class ApiError extends Error {
+  constructor(
+    message: string,
+    public readonly status: number,
+    public readonly requestId?: string,
+  ) {
+    super(message);
+    this.name = "ApiError";
+  }
+}
+
+export interface ClientConfig {
+  baseUrl: string;
+  // Cognito token of the signed-in user, if any.
+  getToken?: () => string | null | undefined | Promise<string | null | undefined>;
+  fetch?: typeof fetch;
+}
+
+type Auth = "none" | "optional" | "required";
+
+async function call<T>(
+  config: ClientConfig,
+  method: string,
+  path: string,
+  auth: Auth,
+  query?: Record<string, string | undefined>,
+  data?: unknown,
+): Promise<T> {
+  const headers: Record<string, string> = {};
+  if (data !== undefined) {
+    headers["Content-Type"] = "application/json";
+  }
+  if (auth !== "none") {
+    const token = config.getToken ? await config.getToken() : undefined;
+    if (token) {
+      headers["Authorization"] = token;
+    } else if (auth === "required") {
+      throw new ApiError("Not signed in.", 401);
+    }
+  }
+  const search = new URLSearchParams();
+  for (const [key, value] of Object.entries(query ?? {})) {
+    if (value !== undefined) {
+      search.append(key, value);
+    }
+  }
+  const url =
+    config.baseUrl.replace(/\/+$/, "") + path + (search.toString() ? "?" + search.toString() : "");
+  const response = await (config.fetch ?? fetch)(url, {
+    method,
+    headers,
+    body: data === undefined ? undefined : JSON.stringify(data),
+  });
+  const text = await response.text();
+  const requestId = response.headers.get("X-Request-Id") ?? undefined;
+  if (response.status < 200 || response.status >= 300) {
+    throw new ApiError(text, response.status, requestId);
+  }
+  if (text === "") {
+    return undefined as T;
+  }
+  const wrapper = JSON.parse(text) as {
+    ok: boolean;
+    data?: T;
+    error?: string;
+    request_id?: string;
+  };
+  if (!wrapper.ok) {
+    throw new ApiError(wrapper.error ?? "Unknown error.", response.status, wrapper.request_id ?? requestId);
+  }
+  return wrapper.data as T;
+}
+"#;
+
+const METHODS: [&str; 4] = ["post", "get", "put", "delete"];
+
+impl OpenApi {
+    pub fn generate_typescript_client(
+        &self,
+        config: &RoutingConfig,
+    ) -> Result<String, ServerError> {
+        Ok(typescript_client(&self.generate(config)?))
+    }
+}
+
+// Helper functions.
+// --------------------------------------------------
+
+fn typescript_client(document: &Value) -> String {
+    let mut out = String::new();
+    out.push_str(&format!(
+        "// Generated from the '{}' API (version {}). Do not edit.\n\n",
+        document["info"]["title"].as_str().unwrap_or_default(),
+        document["info"]["version"].as_str().unwrap_or_default(),
+    ));
+
+    // Types.
+    if let Some(schemas) = document["components"]["schemas"].as_object() {
+        let mut names: Vec<&String> = schemas.keys().collect();
+        names.sort();
+        for name in names {
+            let schema = &schemas[name];
+            write_doc_comment(&mut out, schema["description"].as_str(), "");
+            out.push_str(&format!(
+                "export type {} = {};\n\n",
+                type_name(name),
+                ts_type(schema)
+            ));
+        }
+    }
+
+    out.push_str(CLIENT_RUNTIME);
+
+    // Client.
+    out.push_str("\nexport function createClient(config: ClientConfig) {\n  return {\n");
+    if let Some(paths) = document["paths"].as_object() {
+        let mut sorted_paths: Vec<&String> = paths.keys().collect();
+        sorted_paths.sort();
+        for path in sorted_paths {
+            for method in METHODS {
+                if let Some(operation) = paths[path].get(method) {
+                    write_operation(&mut out, path, method, operation);
+                }
+            }
+        }
+    }
+    out.push_str("  };\n}\n");
+    out
+}
+
+fn write_operation(out: &mut String, path: &str, method: &str, operation: &Value) {
+    let security = operation["security"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+    let auth = match (
+        security.is_empty(),
+        security
+            .iter()
+            .any(|s| s.as_object().is_some_and(|s| s.is_empty())),
+    ) {
+        (true, _) => "none",
+        (false, true) => "optional",
+        (false, false) => "required",
+    };
+
+    let mut params = Vec::new();
+    let mut data_arg = "undefined";
+    if let Some(content) = operation["requestBody"]["content"].as_object() {
+        // Requests are always sent as JSON.
+        let request_type = content
+            .get("application/json")
+            .map(|media| ts_type(&media["schema"]))
+            .unwrap_or_else(|| "unknown".to_string());
+        params.push(format!("data: {}", request_type));
+        data_arg = "data";
+    }
+    let mut query_arg = "undefined";
+    if let Some(parameters) = operation["parameters"].as_array() {
+        let fields: Vec<String> = parameters
+            .iter()
+            .filter(|p| p["in"] == "query")
+            .filter_map(|p| {
+                let name = p["name"].as_str()?;
+                let optional = match p["required"].as_bool().unwrap_or(false) {
+                    true => "",
+                    false => "?",
+                };
+                Some(format!("{}{}: string", property_name(name), optional))
+            })
+            .collect();
+        if !fields.is_empty() {
+            params.push(format!("query: {{ {} }}", fields.join("; ")));
+            query_arg = "query";
+        }
+    }
+    let response_type = ts_type(
+        &operation["responses"]["200"]["content"]["application/json"]["schema"]["oneOf"][0]
+            ["properties"]["data"],
+    );
+
+    let doc = match (
+        operation["summary"].as_str(),
+        operation["description"].as_str(),
+    ) {
+        (Some(summary), Some(description)) => Some(format!("{}\n\n{}", summary, description)),
+        (Some(text), None) | (None, Some(text)) => Some(text.to_string()),
+        (None, None) => None,
+    };
+    write_doc_comment(out, doc.as_deref(), "    ");
+    out.push_str(&format!(
+        "    {}: ({}): Promise<{}> =>\n      call(config, \"{}\", {}, \"{}\", {}, {}),\n",
+        camel_case(operation["operationId"].as_str().unwrap_or(path)),
+        params.join(", "),
+        response_type,
+        method.to_uppercase(),
+        Value::String(path.to_string()),
+        auth,
+        query_arg,
+        data_arg,
+    ));
+}
+
+// Converts a JSON schema (as generated by schemars) into a TypeScript type.
+fn ts_type(schema: &Value) -> String {
+    let object = match schema {
+        Value::Object(object) => object,
+        Value::Bool(false) => return "never".to_string(),
+        _ => return "unknown".to_string(),
+    };
+    if let Some(reference) = object.get("$ref").and_then(Value::as_str) {
+        return type_name(reference.rsplit('/').next().unwrap_or(reference));
+    }
+    if let Some(value) = object.get("const") {
+        return value.to_string();
+    }
+    if let Some(values) = object.get("enum").and_then(Value::as_array) {
+        return union(values.iter().map(Value::to_string).collect());
+    }
+    for (keyword, separator) in [("anyOf", " | "), ("oneOf", " | "), ("allOf", " & ")] {
+        if let Some(schemas) = object.get(keyword).and_then(Value::as_array) {
+            let types: Vec<String> = schemas.iter().map(ts_type).collect();
+            return match types.len() {
+                1 => types[0].clone(),
+                _ => format!("({})", types.join(separator)),
+            };
+        }
+    }
+    match object.get("type") {
+        Some(Value::String(t)) => ts_primitive(t, object),
+        Some(Value::Array(types)) => union(
+            types
+                .iter()
+                .filter_map(Value::as_str)
+                .map(|t| ts_primitive(t, object))
+                .collect(),
+        ),
+        _ => "unknown".to_string(),
+    }
+}
+
+fn ts_primitive(json_type: &str, schema: &serde_json::Map<String, Value>) -> String {
+    match json_type {
+        "string" => "string".to_string(),
+        "integer" | "number" => "number".to_string(),
+        "boolean" => "boolean".to_string(),
+        "null" => "null".to_string(),
+        "array" => match schema.get("items") {
+            // Tuples.
+            Some(Value::Array(items)) => format!(
+                "[{}]",
+                items.iter().map(ts_type).collect::<Vec<_>>().join(", ")
+            ),
+            Some(items) => format!("Array<{}>", ts_type(items)),
+            None => "unknown[]".to_string(),
+        },
+        "object" => {
+            let required: Vec<&str> = schema
+                .get("required")
+                .and_then(Value::as_array)
+                .map(|r| r.iter().filter_map(Value::as_str).collect())
+                .unwrap_or_default();
+            let mut fields: Vec<String> = Vec::new();
+            if let Some(properties) = schema.get("properties").and_then(Value::as_object) {
+                let mut names: Vec<&String> = properties.keys().collect();
+                names.sort();
+                for name in names {
+                    let optional = match required.contains(&name.as_str()) {
+                        true => "",
+                        false => "?",
+                    };
+                    fields.push(format!(
+                        "{}{}: {}",
+                        property_name(name),
+                        optional,
+                        ts_type(&properties[name])
+                    ));
+                }
+            }
+            match schema.get("additionalProperties") {
+                Some(Value::Bool(false)) | None if !fields.is_empty() => {}
+                Some(Value::Bool(false)) => return "Record<string, never>".to_string(),
+                Some(Value::Bool(true)) | None => fields.push("[key: string]: unknown".to_string()),
+                Some(values) => fields.push(format!("[key: string]: {}", ts_type(values))),
+            }
+            format!("{{ {} }}", fields.join("; "))
+        }
+        _ => "unknown".to_string(),
+    }
+}
+
+fn union(mut types: Vec<String>) -> String {
+    types.dedup();
+    match types.len() {
+        0 => "never".to_string(),
+        1 => types.remove(0),
+        _ => types.join(" | "),
+    }
+}
+
+fn write_doc_comment(out: &mut String, text: Option<&str>, indent: &str) {
+    if let Some(text) = text {
+        out.push_str(&format!("{}/**\n", indent));
+        for line in text.lines() {
+            let line = format!("{} * {}", indent, line.replace("*/", "*\\/"));
+            out.push_str(line.trim_end());
+            out.push('\n');
+        }
+        out.push_str(&format!("{} */\n", indent));
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    name.chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '$')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+}
+
+fn property_name(name: &str) -> String {
+    match is_identifier(name) {
+        true => name.to_string(),
+        false => Value::String(name.to_string()).to_string(),
+    }
+}
+
+fn type_name(schema_name: &str) -> String {
+    let name: String = schema_name
+        .chars()
+        .map(|c| match c.is_ascii_alphanumeric() {
+            true => c,
+            false => '_',
+        })
+        .collect();
+    match is_identifier(&name) {
+        true => name,
+        false => format!("_{}", name),
+    }
+}
+
+// "orders_create-bulk" -> "ordersCreateBulk".
+fn camel_case(operation_id: &str) -> String {
+    let mut words = operation_id
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty());
+    let mut name = words.next().unwrap_or("call").to_string();
+    for word in words {
+        let mut chars = word.chars();
+        if let Some(first) = chars.next() {
+            name.push(first.to_ascii_uppercase());
+            name.push_str(chars.as_str());
+        }
+    }
+    match name.starts_with(|c: char| c.is_ascii_digit()) {
+        true => format!("_{}", name),
+        false => name,
+    }
+}
+
+// Tests.
+// --------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        openapi::RouteDocs,
+        routing::{box_route_handler, AccessLevel, CrudRoute},
+        testing::{ok_handler, ok_route},
+    };
+    use schemars::JsonSchema;
+    use serde_json::json;
+
+    #[allow(dead_code)]
+    #[derive(JsonSchema)]
+    struct CreateOrderRequest {
+        item: String,
+        quantity: u32,
+        note: Option<String>,
+    }
+
+    #[allow(dead_code)]
+    #[derive(JsonSchema)]
+    enum OrderStatus {
+        Pending,
+        Shipped,
+    }
+
+    fn generate() -> String {
+        let config = RoutingConfig::builder()
+            .function("orders/create", ok_route(AccessLevel::User))
+            .crud(
+                "items",
+                CrudRoute {
+                    create_access_level: AccessLevel::None,
+                    read_access_level: AccessLevel::Guest,
+                    update_access_level: AccessLevel::None,
+                    delete_access_level: AccessLevel::None,
+                    handler: box_route_handler(ok_handler),
+                    timeout: None,
+                    body_config: Default::default(),
+                    field_selection: Default::default(),
+                    cache_control: None,
+                },
+            )
+            .build()
+            .unwrap();
+        OpenApi::new("Orders API", "1.0.0")
+            .route(
+                "orders/create",
+                RouteDocs::new()
+                    .summary("Create an order")
+                    .request::<CreateOrderRequest>()
+                    .response::<OrderStatus>(),
+            )
+            .route("items", RouteDocs::crud_scaffolding())
+            .generate_typescript_client(&config)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_generate_client() {
+        let source = generate();
+        assert!(source.contains(
+            "export type CreateOrderRequest = { item: string; note?: string | null; quantity: number };"
+        ));
+        assert!(source.contains("export type OrderStatus = \"Pending\" | \"Shipped\";"));
+        assert!(source.contains(
+            "    /**\n     * Create an order\n     */\n    ordersCreate: (data: CreateOrderRequest): Promise<OrderStatus> =>\n      call(config, \"POST\", \"/orders/create\", \"required\", undefined, data),\n"
+        ));
+        assert!(source.contains(
+            "    itemsRead: (query: { id: string }): Promise<unknown> =>\n      call(config, \"GET\", \"/items\", \"optional\", query, undefined),\n"
+        ));
+        assert!(!source.contains("itemsCreate"));
+        // Deterministic.
+        assert_eq!(source, generate());
+    }
+
+    #[test]
+    fn test_ts_type() {
+        assert_eq!(
+            ts_type(&json!({ "type": ["integer", "null"] })),
+            "number | null"
+        );
+        assert_eq!(
+            ts_type(&json!({ "type": "array", "items": { "$ref": "#/components/schemas/Foo" } })),
+            "Array<Foo>"
+        );
+        assert_eq!(
+            ts_type(&json!({ "type": "object", "additionalProperties": { "type": "string" } })),
+            "{ [key: string]: string }"
+        );
+        assert_eq!(
+            ts_type(
+                &json!({ "type": "object", "properties": { "cognito:groups": { "type": "string" } }, "required": ["cognito:groups"] })
+            ),
+            "{ \"cognito:groups\": string }"
+        );
+        assert_eq!(ts_type(&json!({})), "unknown");
+    }
+}
@@ -0,0 +1,366 @@
+use std::{fmt::Display, ops::RangeBounds};
+
+use fractic_server_error::ServerError;
+use regex::Regex;
+
+use crate::errors::ValidationError;
+
+// Request validation.
+// --------------------------------------------------
+//
+// Request data types can implement Validate to declare rules for their
+// fields, for example:
+//
+//   impl Validate for CreateOrder {
+//       fn validate(&self, v: &mut Validator) {
+//           v.length("title", &self.title, 1..=100);
+//           v.range("quantity", self.quantity, 1..=50);
+//           v.email("contact", &self.contact);
+//           v.each("items", &self.items);
+//       }
+//   }
+//
+// The registration macros run the rules automatically after parsing the
+// request data (for types implementing Validate), and all violations are
+// returned to the client in a single ValidationError.
+
+pub trait Validate {
+    fn validate(&self, v: &mut Validator);
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    // Path of the failing field, for example "items[2].name".
+    pub path: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Default)]
+pub struct Validator {
+    prefix: String,
+    violations: Vec<Violation>,
+}
+
+impl Validator {
+    pub fn violations(&self) -> &[Violation] {
+        &self.violations
+    }
+
+    pub fn add_violation(&mut self, field: &str, reason: impl Into<String>) {
+        self.violations.push(Violation {
+            path: self.path(field),
+            reason: reason.into(),
+        });
+    }
+
+    pub fn check(&mut self, field: &str, condition: bool, reason: &str) {
+        if !condition {
+            self.add_violation(field, reason);
+        }
+    }
+
+    pub fn required<T>(&mut self, field: &str, value: &Option<T>) {
+        self.check(field, value.is_some(), "is required");
+    }
+
+    // Length of a string, in characters.
+    pub fn length(&mut self, field: &str, value: &str, bounds: impl RangeBounds<usize>) {
+        let length = value.chars().count();
+        if !bounds.contains(&length) {
+            let reason = format!("length must be {}", describe_bounds(&bounds));
+            self.add_violation(field, reason);
+        }
+    }
+
+    // Number of items in a collection.
+    pub fn count<T>(&mut self, field: &str, value: &[T], bounds: impl RangeBounds<usize>) {
+        if !bounds.contains(&value.len()) {
+            let reason = format!("number of items must be {}", describe_bounds(&bounds));
+            self.add_violation(field, reason);
+        }
+    }
+
+    pub fn range<T>(&mut self, field: &str, value: T, bounds: impl RangeBounds<T>)
+    where
+        T: PartialOrd + Display,
+    {
+        if !bounds.contains(&value) {
+            let reason = format!("must be {}", describe_bounds(&bounds));
+            self.add_violation(field, reason);
+        }
+    }
+
+    pub fn regex(&mut self, field: &str, value: &str, regex: &Regex) {
+        if !regex.is_match(value) {
+            self.add_violation(field, format!("must match the pattern '{}'", regex));
+        }
+    }
+
+    // Basic structural check (local@domain.tld). Whether the address actually
+    // exists can only be checked by sending an email.
+    pub fn email(&mut self, field: &str, value: &str) {
+        let is_valid = match value.split_once('@') {
+            Some((local, domain)) => {
+                !local.is_empty()
+                    && !domain.contains('@')
+                    && domain.split('.').count() >= 2
+                    && domain.split('.').all(|label| !label.is_empty())
+                    && !value.chars().any(char::is_whitespace)
+            }
+            None => false,
+        };
+        self.check(field, is_valid, "must be a valid email address");
+    }
+
+    pub fn one_of<T>(&mut self, field: &str, value: &T, allowed: &[T])
+    where
+        T: PartialEq + Display,
+    {
+        if !allowed.contains(value) {
+            let allowed = allowed
+                .iter()
+                .map(|a| a.to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            self.add_violation(field, format!("must be one of [{}]", allowed));
+        }
+    }
+
+    // Runs the rules of a nested object, prefixing its paths with the field.
+    pub fn nested(&mut self, field: &str, value: &impl Validate) {
+        let nested_prefix = self.path(field);
+        let outer_prefix = std::mem::replace(&mut self.prefix, nested_prefix);
+        value.validate(self);
+        self.prefix = outer_prefix;
+    }
+
+    // Runs the rules of each item in a collection, prefixing their paths with
+    // the field and index.
+    pub fn each<'a, T: Validate + 'a>(
+        &mut self,
+        field: &str,
+        values: impl IntoIterator<Item = &'a T>,
+    ) {
+        for (i, value) in values.into_iter().enumerate() {
+            self.nested(&format!("{}[{}]", field, i), value);
+        }
+    }
+
+    fn path(&self, field: &str) -> String {
+        if self.prefix.is_empty() {
+            field.to_string()
+        } else if field.starts_with('[') {
+            format!("{}{}", self.prefix, field)
+        } else {
+            format!("{}.{}", self.prefix, field)
+        }
+    }
+}
+
+impl<T: Validate> Validate for Option<T> {
+    fn validate(&self, v: &mut Validator) {
+        if let Some(value) = self {
+            value.validate(v);
+        }
+    }
+}
+
+impl<T: Validate> Validate for Vec<T> {
+    fn validate(&self, v: &mut Validator) {
+        for (i, value) in self.iter().enumerate() {
+            v.nested(&format!("[{}]", i), value);
+        }
+    }
+}
+
+// Runs the object's rules, collecting all violations into a single error.
+pub fn validate<T: Validate>(obj: &T) -> Result<(), ServerError> {
+    let mut validator = Validator::default();
+    obj.validate(&mut validator);
+    if validator.violations.is_empty() {
+        Ok(())
+    } else {
+        let details = validator
+            .violations
+            .iter()
+            .map(|v| format!("'{}' {}", v.path, v.reason))
+            .collect::<Vec<_>>()
+            .join("; ");
+        Err(ValidationError::new(&details))
+    }
+}
+
+// Used by the registration macros to run validation only for request data
+// types which implement Validate (through autoref-based specialization, since
+// the concrete type is known at the macro call site).
+#[doc(hidden)]
+pub struct ValidationWrap<'a, T>(pub &'a T);
+
+#[doc(hidden)]
+pub trait ValidateIfImplemented {
+    fn validate_if_implemented(&self) -> Result<(), ServerError>;
+}
+
+impl<T: Validate> ValidateIfImplemented for ValidationWrap<'_, T> {
+    fn validate_if_implemented(&self) -> Result<(), ServerError> {
+        validate(self.0)
+    }
+}
+
+#[doc(hidden)]
+pub trait ValidateFallback {
+    fn validate_if_implemented(&self) -> Result<(), ServerError>;
+}
+
+impl<T> ValidateFallback for &ValidationWrap<'_, T> {
+    fn validate_if_implemented(&self) -> Result<(), ServerError> {
+        Ok(())
+    }
+}
+
+// Helper functions.
+// --------------------------------------------------
+
+fn describe_bounds<T: Display>(bounds: &impl RangeBounds<T>) -> String {
+    use std::ops::Bound::*;
+    match (bounds.start_bound(), bounds.end_bound()) {
+        (Included(a), Included(b)) => format!("between {} and {}", a, b),
+        (Included(a), Excluded(b)) => format!("at least {} and less than {}", a, b),
+        (Included(a), Unbounded) => format!("at least {}", a),
+        (Excluded(a), Included(b)) => format!("more than {} and at most {}", a, b),
+        (Excluded(a), Excluded(b)) => format!("more than {} and less than {}", a, b),
+        (Excluded(a), Unbounded) => format!("more than {}", a),
+        (Unbounded, Included(b)) => format!("at most {}", b),
+        (Unbounded, Excluded(b)) => format!("less than {}", b),
+        (Unbounded, Unbounded) => "any value".to_string(),
+    }
+}
+
+// Tests.
+// --------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Item {
+        name: String,
+    }
+
+    impl Validate for Item {
+        fn validate(&self, v: &mut Validator) {
+            v.length("name", &self.name, 1..=10);
+        }
+    }
+
+    struct Order {
+        title: String,
+        quantity: u32,
+        contact: String,
+        status: String,
+        code: String,
+        items: Vec<Item>,
+    }
+
+    impl Validate for Order {
+        fn validate(&self, v: &mut Validator) {
+            v.length("title", &self.title, 1..=20);
+            v.range("quantity", self.quantity, 1..=50);
+            v.email("contact", &self.contact);
+            v.one_of("status", &self.status.as_str(), &["draft", "submitted"]);
+            v.regex("code", &self.code, &Regex::new("^[A-Z]{3}$").unwrap());
+            v.each("items", &self.items);
+        }
+    }
+
+    fn create_valid_order() -> Order {
+        Order {
+            title: "Order".to_string(),
+            quantity: 3,
+            contact: "jane@example.com".to_string(),
+            status: "draft".to_string(),
+            code: "ABC".to_string(),
+            items: vec![Item {
+                name: "Item".to_string(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_valid_object() {
+        assert!(validate(&create_valid_order()).is_ok());
+    }
+
+    #[test]
+    fn test_all_violations_collected() {
+        let order = Order {
+            title: "".to_string(),
+            quantity: 51,
+            contact: "not an email".to_string(),
+            status: "deleted".to_string(),
+            code: "abc".to_string(),
+            items: vec![
+                Item {
+                    name: "Item".to_string(),
+                },
+                Item {
+                    name: "".to_string(),
+                },
+            ],
+        };
+        let mut validator = Validator::default();
+        order.validate(&mut validator);
+        let paths: Vec<&str> = validator
+            .violations()
+            .iter()
+            .map(|v| v.path.as_str())
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                "title",
+                "quantity",
+                "contact",
+                "status",
+                "code",
+                "items[1].name"
+            ]
+        );
+
+        let err = validate(&order).unwrap_err();
+        assert!(err
+            .message()
+            .contains("'items[1].name' length must be between 1 and 10"));
+    }
+
+    #[test]
+    fn test_email() {
+        let mut validator = Validator::default();
+        validator.email("a", "jane@example.com");
+        validator.email("b", "jane@example");
+        validator.email("c", "@example.com");
+        validator.email("d", "jane@@example.com");
+        let paths: Vec<&str> = validator
+            .violations()
+            .iter()
+            .map(|v| v.path.as_str())
+            .collect();
+        assert_eq!(paths, vec!["b", "c", "d"]);
+    }
+
+    // Calls are written as in validate_request_data!, where the borrow is
+    // required to fall back for types without rules.
+    #[allow(clippy::needless_borrow)]
+    #[test]
+    fn test_validate_if_implemented() {
+        let order = Order {
+            quantity: 0,
+            ..create_valid_order()
+        };
+        assert!((&ValidationWrap(&order)).validate_if_implemented().is_err());
+        // Types without rules are always valid.
+        assert!((&ValidationWrap(&"no rules"))
+            .validate_if_implemented()
+            .is_ok());
+    }
+}
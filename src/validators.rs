@@ -0,0 +1,210 @@
+use std::{future::Future, pin::Pin};
+
+use aws_lambda_events::apigw::ApiGatewayProxyRequest;
+use fractic_server_error::ServerError;
+
+use crate::{errors::UnauthorizedError, request::RequestMetadata};
+
+// Async request validators.
+// --------------------------------------------------
+//
+// Unlike the synchronous $validator of register_function_route!, async
+// validators receive the full request and the route's shared state, so they
+// can inspect headers or look up the database, for example:
+//
+//   fn is_team_member<'a>(
+//       ctx: &'a ValidationContext<'a, CreateOrder, AppState>,
+//   ) -> ValidatorFuture<'a> {
+//       Box::pin(async move {
+//           let sub = ctx.metadata.user_sub.as_deref().unwrap_or_default();
+//           ctx.state.teams.verify_member(&ctx.data.team_id, sub).await
+//       })
+//   }
+//
+//   register_function_route!(
+//       create_order_handler,
+//       create_order,
+//       async all_of(vec![Box::new(require_authenticated), Box::new(is_team_member)]),
+//       CreateOrder,
+//       state: &*APP_STATE
+//   );
+//
+// Validators run after the request data is parsed and its declarative rules
+// (Validate) pass, and before the route function is called.
+
+pub type ValidatorFuture<'a> = Pin<Box<dyn Future<Output = Result<(), ServerError>> + Send + 'a>>;
+
+pub struct ValidationContext<'a, T, S = ()> {
+    pub request: &'a ApiGatewayProxyRequest,
+    pub metadata: &'a RequestMetadata,
+    // Parsed request data, or () for routes without request data.
+    pub data: &'a T,
+    pub state: &'a S,
+}
+
+pub trait AsyncValidator<T, S = ()>: Send + Sync {
+    fn validate<'a>(&'a self, ctx: &'a ValidationContext<'a, T, S>) -> ValidatorFuture<'a>;
+}
+
+impl<T, S, F> AsyncValidator<T, S> for F
+where
+    F: for<'a> Fn(&'a ValidationContext<'a, T, S>) -> ValidatorFuture<'a> + Send + Sync,
+{
+    fn validate<'a>(&'a self, ctx: &'a ValidationContext<'a, T, S>) -> ValidatorFuture<'a> {
+        self(ctx)
+    }
+}
+
+pub type BoxedValidator<T, S = ()> = Box<dyn AsyncValidator<T, S>>;
+
+// Passes if all validators pass. Validators run in order, stopping at the
+// first failure, so cheap checks should be listed before expensive ones.
+pub struct AllOf<T, S = ()>(Vec<BoxedValidator<T, S>>);
+
+pub fn all_of<T, S>(validators: Vec<BoxedValidator<T, S>>) -> AllOf<T, S> {
+    AllOf(validators)
+}
+
+impl<T: Sync, S: Sync> AsyncValidator<T, S> for AllOf<T, S> {
+    fn validate<'a>(&'a self, ctx: &'a ValidationContext<'a, T, S>) -> ValidatorFuture<'a> {
+        Box::pin(async move {
+            for validator in &self.0 {
+                validator.validate(ctx).await?;
+            }
+            Ok(())
+        })
+    }
+}
+
+// Passes if any validator passes. Validators run in order, stopping at the
+// first success. If all fail, the error of the first one is returned.
+pub struct AnyOf<T, S = ()>(Vec<BoxedValidator<T, S>>);
+
+pub fn any_of<T, S>(validators: Vec<BoxedValidator<T, S>>) -> AnyOf<T, S> {
+    AnyOf(validators)
+}
+
+impl<T: Sync, S: Sync> AsyncValidator<T, S> for AnyOf<T, S> {
+    fn validate<'a>(&'a self, ctx: &'a ValidationContext<'a, T, S>) -> ValidatorFuture<'a> {
+        Box::pin(async move {
+            let mut first_error = None;
+            for validator in &self.0 {
+                match validator.validate(ctx).await {
+                    Ok(_) => return Ok(()),
+                    Err(error) => {
+                        first_error.get_or_insert(error);
+                    }
+                }
+            }
+            Err(first_error.unwrap_or_else(|| {
+                UnauthorizedError::with_debug(&"any_of has no validators".to_string())
+            }))
+        })
+    }
+}
+
+// Common validators.
+// --------------------------------------------------
+
+pub fn require_authenticated<'a, T: Sync, S: Sync>(
+    ctx: &'a ValidationContext<'a, T, S>,
+) -> ValidatorFuture<'a> {
+    Box::pin(async move {
+        match ctx.metadata.is_authenticated {
+            true => Ok(()),
+            false => Err(UnauthorizedError::with_debug(
+                &"request is not authenticated".to_string(),
+            )),
+        }
+    })
+}
+
+pub fn require_admin<'a, T: Sync, S: Sync>(
+    ctx: &'a ValidationContext<'a, T, S>,
+) -> ValidatorFuture<'a> {
+    Box::pin(async move {
+        match ctx.metadata.is_admin {
+            true => Ok(()),
+            false => Err(UnauthorizedError::with_debug(
+                &"user is not an admin".to_string(),
+            )),
+        }
+    })
+}
+
+// Tests.
+// --------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::correlation::CorrelationIds;
+
+    struct TeamState {
+        members: Vec<String>,
+    }
+
+    fn is_team_member<'a>(
+        ctx: &'a ValidationContext<'a, String, TeamState>,
+    ) -> ValidatorFuture<'a> {
+        Box::pin(async move {
+            match ctx.state.members.contains(ctx.data) {
+                true => Ok(()),
+                false => Err(UnauthorizedError::with_debug(&"not a member".to_string())),
+            }
+        })
+    }
+
+    fn create_metadata(is_admin: bool) -> RequestMetadata {
+        RequestMetadata {
+            is_authenticated: true,
+            is_admin,
+            user_sub: Some("sub".to_string()),
+            correlation_ids: CorrelationIds::from_request(&ApiGatewayProxyRequest::default()),
+            deadline: None,
+        }
+    }
+
+    async fn run(
+        validator: &impl AsyncValidator<String, TeamState>,
+        user: &str,
+        is_admin: bool,
+    ) -> Result<(), ServerError> {
+        let request = ApiGatewayProxyRequest::default();
+        let metadata = create_metadata(is_admin);
+        let state = TeamState {
+            members: vec!["jane".to_string()],
+        };
+        let data = user.to_string();
+        let ctx = ValidationContext {
+            request: &request,
+            metadata: &metadata,
+            data: &data,
+            state: &state,
+        };
+        validator.validate(&ctx).await
+    }
+
+    #[tokio::test]
+    async fn test_all_of() {
+        let validator = all_of(vec![Box::new(require_admin), Box::new(is_team_member)]);
+        assert!(run(&validator, "jane", true).await.is_ok());
+        assert!(run(&validator, "jane", false).await.is_err());
+        assert!(run(&validator, "john", true).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_any_of() {
+        let validator = any_of(vec![Box::new(require_admin), Box::new(is_team_member)]);
+        assert!(run(&validator, "john", true).await.is_ok());
+        assert!(run(&validator, "jane", false).await.is_ok());
+        let err = run(&validator, "john", false).await.unwrap_err();
+        assert!(format!("{:?}", err).contains("not an admin"));
+    }
+
+    #[tokio::test]
+    async fn test_any_of_empty_fails() {
+        let validator: AnyOf<String, TeamState> = any_of(vec![]);
+        assert!(run(&validator, "jane", true).await.is_err());
+    }
+}